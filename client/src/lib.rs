@@ -1,5 +1,5 @@
 use {
-    client_api::{plugin::{PluginData, PluginEventData, PluginTrait}, result::EventResult, style::Style}, leptos::{view, IntoView, View}, serde::Deserialize
+    chrono::{DateTime, FixedOffset, Local, Utc}, client_api::{plugin::{PluginData, PluginEventData, PluginTrait}, result::EventResult, style::Style}, leptos::{view, IntoView, View}, serde::Deserialize
 };
 
 pub struct Plugin {
@@ -18,24 +18,361 @@ impl PluginTrait for Plugin {
     }
 
     fn get_component(&self, data: PluginEventData) -> EventResult<Box<dyn FnOnce() -> leptos::View>> {
-        let data = data.get_data::<DatabaseCommit>()?;
+        let data = data.get_data::<GitEvent>()?;
         Ok(Box::new(
             move || -> View {
-                view! {
-                    <div style="color: var(--lightColor); padding: calc(var(--contentSpacing) * 0.5); display: flex; flex-direction: column; width: 100%; gap: calc(var(--contentSpacing) * 0.5); background-color: var(--accentColor1);align-items: start; box-sizing: border-box;">
-                        <h3>{move || { data.repository_name.clone() }}</h3>
-                        <a>{move || { data.message.clone() }}</a>
-                        <a>{move || { data.author.clone() }}</a>
-                    </div>
-                }.into_view()
+                match data {
+                    GitEvent::Commit(commit) => commit_view(commit),
+                    GitEvent::Session(session) => session_view(session),
+                    GitEvent::Group(group) => group_view(group),
+                    GitEvent::Tag(tag) => tag_view(tag),
+                    GitEvent::Reflog(entry) => reflog_view(entry),
+                }
             }
         ))
     }
 }
 
+fn commit_view(data: DatabaseCommit) -> View {
+    let author = format_identity(&data.author_name, &data.author_email);
+    let dates = match (data.author_time, data.committer_time) {
+        (Some(authored), Some(committed)) if authored != committed => Some(format!(
+            "authored {}, committed {}",
+            relative_day(authored),
+            relative_day(committed)
+        )),
+        _ => None,
+    };
+    let local_time = match (data.author_time, data.author_offset_minutes) {
+        (Some(time), Some(offset)) => FixedOffset::east_opt(offset * 60).map(|offset| {
+            format!(
+                "{} local time of author (UTC{})",
+                time.with_timezone(&offset).format("%H:%M"),
+                offset
+            )
+        }),
+        _ => None,
+    };
+    let rewritten = match (data.rewritten, &data.replaced_by) {
+        (true, Some(replaced_by)) => {
+            let oid = replaced_by.rsplit(':').next().unwrap_or_default();
+            Some(format!("rewritten, replaced by {}", &oid[..oid.len().min(7)]))
+        }
+        (true, None) => Some("rewritten".to_string()),
+        (false, _) => None,
+    };
+    let co_authors: Vec<String> = data
+        .trailers
+        .iter()
+        .filter(|v| v.key.eq_ignore_ascii_case("co-authored-by"))
+        .map(|v| v.value.clone())
+        .collect();
+    let co_authors = match co_authors.is_empty() {
+        true => None,
+        false => Some(format!("with {}", co_authors.join(", "))),
+    };
+    let subject = subject_line(&data);
+    view! {
+        <div style="color: var(--lightColor); padding: calc(var(--contentSpacing) * 0.5); display: flex; flex-direction: column; width: 100%; gap: calc(var(--contentSpacing) * 0.5); background-color: var(--accentColor1);align-items: start; box-sizing: border-box;">
+            <h3>{move || { data.repository_name.clone() }}</h3>
+            {rewritten.map(|rewritten| view! { <a style="font-style: italic;">{rewritten}</a> })}
+            {subject}
+            {data.body.clone().map(|body| view! { <a style="white-space: pre-wrap;">{body}</a> })}
+            {data.diff.map(diff_bar)}
+            <a>{move || { author.clone() }}</a>
+            {co_authors.map(|co_authors| view! { <a>{co_authors}</a> })}
+            {reference_links(data.references.clone())}
+            {dates.map(|dates| view! { <a>{dates}</a> })}
+            {local_time.map(|local_time| view! { <a>{local_time}</a> })}
+            <a>{move || { data.refs.join(", ") }}</a>
+        </div>
+    }.into_view()
+}
+
+fn session_view(data: WorkSession) -> View {
+    let header = format!(
+        "{} \u{00b7} {} \u{2013} {}",
+        format_identity(&data.author_name, &data.author_email),
+        data.start.with_timezone(&Local).format("%H:%M"),
+        data.end.with_timezone(&Local).format("%H:%M"),
+    );
+    view! {
+        <div style="color: var(--lightColor); padding: calc(var(--contentSpacing) * 0.5); display: flex; flex-direction: column; width: 100%; gap: calc(var(--contentSpacing) * 0.5); background-color: var(--accentColor1);align-items: start; box-sizing: border-box;">
+            <h3>{data.repository_name}</h3>
+            <a>{header}</a>
+            {commit_list(data.commits)}
+        </div>
+    }.into_view()
+}
+
+fn group_view(data: CommitGroup) -> View {
+    let header = format!(
+        "{} commits in {}",
+        data.commits.len(),
+        data.repository_name
+    );
+    let span = format!(
+        "{} \u{2013} {}",
+        data.start.with_timezone(&Local).format("%H:%M"),
+        data.end.with_timezone(&Local).format("%H:%M"),
+    );
+    view! {
+        <div style="color: var(--lightColor); padding: calc(var(--contentSpacing) * 0.5); display: flex; flex-direction: column; width: 100%; gap: calc(var(--contentSpacing) * 0.5); background-color: var(--accentColor1);align-items: start; box-sizing: border-box;">
+            <h3>{header}</h3>
+            <a>{span}</a>
+            {commit_list(data.commits)}
+        </div>
+    }.into_view()
+}
+
+fn tag_view(data: DatabaseTag) -> View {
+    let tagger = match (data.tagger_name, data.tagger_email) {
+        (Some(name), Some(email)) => Some(format_identity(&name, &email)),
+        (Some(name), None) => Some(name),
+        (None, Some(email)) => Some(email),
+        (None, None) => None,
+    };
+    let target = data.target.chars().take(10).collect::<String>();
+    view! {
+        <div style="color: var(--lightColor); padding: calc(var(--contentSpacing) * 0.5); display: flex; flex-direction: column; width: 100%; gap: calc(var(--contentSpacing) * 0.5); background-color: var(--accentColor2); border-left: calc(var(--contentSpacing) * 0.5) solid var(--accentColor1); align-items: start; box-sizing: border-box;">
+            <h3>{format!("{} {}", data.repository_name, data.tag_name)}</h3>
+            {data.message.map(|message| view! { <a>{message}</a> })}
+            {tagger.map(|tagger| view! { <a>{tagger}</a> })}
+            <a>{format!("{} tag on {}", if data.annotated { "Annotated" } else { "Lightweight" }, target)}</a>
+        </div>
+    }.into_view()
+}
+
+fn reflog_view(data: DatabaseReflogEntry) -> View {
+    let action = match data.action.as_str() {
+        "checkout" => "Checked out",
+        "commit" => "Committed",
+        "rebase" => "Rebased",
+        "merge" => "Merged",
+        "pull" => "Pulled",
+        "reset" => "Reset",
+        "fast_forward" => "Fast-forwarded",
+        "created" => "Created",
+        "deleted" => "Deleted",
+        _ => "Moved",
+    };
+    view! {
+        <div style="color: var(--lightColor); padding: calc(var(--contentSpacing) * 0.5); display: flex; flex-direction: column; width: 100%; gap: calc(var(--contentSpacing) * 0.5); background-color: var(--accentColor1);align-items: start; box-sizing: border-box; opacity: 0.8;">
+            <h3>{data.repository_name}</h3>
+            <a>{format!("{} {}", action, data.reference)}</a>
+            <a>{data.message}</a>
+        </div>
+    }.into_view()
+}
+
+fn commit_list(commits: Vec<DatabaseCommit>) -> Vec<View> {
+    commits
+        .into_iter()
+        .map(|commit| {
+            view! {
+                <div style="display: flex; flex-direction: column; width: 100%; gap: calc(var(--contentSpacing) * 0.25); padding-left: calc(var(--contentSpacing) * 0.5); border-left: 2px solid var(--lightColor); box-sizing: border-box;">
+                    {subject_line(&commit)}
+                    {commit.diff.map(diff_bar)}
+                </div>
+            }.into_view()
+        })
+        .collect()
+}
+
+/// The subject, led by a `feat(api)!` badge for Conventional Commits.
+fn subject_line(commit: &DatabaseCommit) -> View {
+    let (badge, text) = match &commit.conventional {
+        Some(v) => (
+            Some(format!(
+                "{}{}{}",
+                v.kind,
+                v.scope.as_ref().map(|v| format!("({})", v)).unwrap_or_default(),
+                if v.breaking { "!" } else { "" }
+            )),
+            v.description.clone(),
+        ),
+        // Commits stored before messages were parsed only have the raw message.
+        None => match commit.subject.is_empty() {
+            true => (None, commit.message.lines().next().unwrap_or_default().to_string()),
+            false => (None, commit.subject.clone()),
+        },
+    };
+    view! {
+        <div style="display: flex; flex-direction: row; gap: calc(var(--contentSpacing) * 0.5); align-items: center;">
+            {badge.map(|badge| view! {
+                <span style="padding: 0 calc(var(--contentSpacing) * 0.25); border: 1px solid var(--lightColor); border-radius: calc(var(--contentSpacing) * 0.25); font-weight: bold;">{badge}</span>
+            })}
+            <a>{text}</a>
+        </div>
+    }.into_view()
+}
+
+fn reference_links(references: Vec<IssueReference>) -> Option<View> {
+    if references.is_empty() {
+        return None;
+    }
+    Some(view! {
+        <div style="display: flex; flex-direction: row; flex-wrap: wrap; gap: calc(var(--contentSpacing) * 0.5);">
+            {references
+                .into_iter()
+                .map(|reference| match reference.url {
+                    Some(url) => view! { <a href=url target="_blank" style="color: var(--lightColor);">{reference.text}</a> }.into_view(),
+                    None => view! { <a>{reference.text}</a> }.into_view(),
+                })
+                .collect::<Vec<_>>()}
+        </div>
+    }.into_view())
+}
+
+fn format_identity(name: &str, email: &str) -> String {
+    match (name.is_empty(), email.is_empty()) {
+        (false, false) => format!("{} <{}>", name, email),
+        (false, true) => name.to_string(),
+        (true, _) => email.to_string(),
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum GitEvent {
+    Commit(DatabaseCommit),
+    Session(WorkSession),
+    Group(CommitGroup),
+    Tag(DatabaseTag),
+    Reflog(DatabaseReflogEntry),
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct WorkSession {
+    repository_name: String,
+    author_name: String,
+    author_email: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    commits: Vec<DatabaseCommit>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct CommitGroup {
+    repository_name: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    commits: Vec<DatabaseCommit>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct DatabaseTag {
+    tag_name: String,
+    repository_name: String,
+    target: String,
+    annotated: bool,
+    #[serde(default)]
+    tagger_name: Option<String>,
+    #[serde(default)]
+    tagger_email: Option<String>,
+    #[serde(default)]
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct DatabaseReflogEntry {
+    repository_name: String,
+    reference: String,
+    action: String,
+    message: String,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 struct DatabaseCommit {
     message: String,
-    author: String,
+    #[serde(default)]
+    author_name: String,
+    #[serde(default)]
+    author_email: String,
+    #[serde(default)]
+    author_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    author_offset_minutes: Option<i32>,
+    #[serde(default)]
+    committer_time: Option<DateTime<Utc>>,
     repository_name: String,
+    #[serde(default)]
+    refs: Vec<String>,
+    #[serde(default)]
+    diff: Option<DiffSummary>,
+    #[serde(default)]
+    rewritten: bool,
+    #[serde(default)]
+    replaced_by: Option<String>,
+    #[serde(default)]
+    subject: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    trailers: Vec<Trailer>,
+    #[serde(default)]
+    conventional: Option<ConventionalCommit>,
+    #[serde(default)]
+    references: Vec<IssueReference>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct IssueReference {
+    text: String,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct Trailer {
+    key: String,
+    value: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct ConventionalCommit {
+    #[serde(rename = "type")]
+    kind: String,
+    scope: Option<String>,
+    breaking: bool,
+    description: String,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct DiffSummary {
+    files_changed: usize,
+    insertions: Option<usize>,
+    deletions: Option<usize>,
+    paths: Vec<String>,
+}
+
+fn diff_bar(diff: DiffSummary) -> View {
+    let files = match diff.files_changed {
+        1 => "1 file".to_string(),
+        v => format!("{} files", v),
+    };
+    let paths = diff.paths.join("\n");
+
+    match (diff.insertions, diff.deletions) {
+        (Some(insertions), Some(deletions)) => view! {
+            <div title=paths style="display: flex; flex-direction: column; width: 100%; gap: calc(var(--contentSpacing) * 0.25);">
+                <a>{format!("{}, +{} \u{2212}{}", files, insertions, deletions)}</a>
+                <div style="display: flex; width: 100%; height: calc(var(--contentSpacing) * 0.5);">
+                    <div style=format!("flex: {} 1 0; background-color: #3fb950;", insertions)></div>
+                    <div style=format!("flex: {} 1 0; background-color: #f85149;", deletions)></div>
+                </div>
+            </div>
+        }.into_view(),
+        _ => view! {
+            <a title=paths>{files}</a>
+        }.into_view(),
+    }
+}
+
+fn relative_day(time: DateTime<Utc>) -> String {
+    match Utc::now().signed_duration_since(time).num_days() {
+        0 => "today".to_string(),
+        1 => "yesterday".to_string(),
+        v => format!("{} days ago", v),
+    }
 }
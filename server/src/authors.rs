@@ -0,0 +1,127 @@
+use {
+    globset::{GlobBuilder, GlobMatcher},
+    regex::{Regex, RegexBuilder},
+};
+
+/// Decides whose commits end up on the timeline.
+///
+/// Every pattern is matched against both the name and the email of an identity:
+/// - `regex:<expression>` is a case-insensitive regular expression,
+/// - `glob:<pattern>` or any pattern containing `*` or `?` is a case-insensitive glob,
+/// - everything else has to match exactly, ignoring case.
+#[derive(Clone, Default)]
+pub struct AuthorFilter {
+    patterns: Vec<AuthorPattern>,
+}
+
+#[derive(Clone)]
+enum AuthorPattern {
+    Exact(String),
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl AuthorFilter {
+    pub fn new(patterns: &[String]) -> Result<Self, String> {
+        let patterns = patterns
+            .iter()
+            .map(|pattern| {
+                if let Some(v) = pattern.strip_prefix("regex:") {
+                    return match RegexBuilder::new(v).case_insensitive(true).build() {
+                        Ok(v) => Ok(AuthorPattern::Regex(v)),
+                        Err(e) => Err(format!("Invalid author regex {}: {}", v, e)),
+                    };
+                }
+
+                let glob = match pattern.strip_prefix("glob:") {
+                    Some(v) => v,
+                    None if pattern.contains(['*', '?']) => pattern,
+                    None => return Ok(AuthorPattern::Exact(pattern.to_lowercase())),
+                };
+
+                match GlobBuilder::new(glob).case_insensitive(true).build() {
+                    Ok(v) => Ok(AuthorPattern::Glob(v.compile_matcher())),
+                    Err(e) => Err(format!("Invalid author glob {}: {}", glob, e)),
+                }
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(AuthorFilter { patterns })
+    }
+
+    /// An empty filter lets every commit through.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    pub fn matches(&self, name: &str, email: &str) -> bool {
+        self.patterns.iter().any(|pattern| {
+            [name, email].into_iter().any(|value| match pattern {
+                AuthorPattern::Exact(v) => value.to_lowercase() == *v,
+                AuthorPattern::Glob(v) => v.is_match(value),
+                AuthorPattern::Regex(v) => v.is_match(value),
+            })
+        })
+    }
+}
+
+/// Splits a `Name <email>` string, the format authors were stored in before
+/// identities were kept as separate fields.
+pub fn split_signature(signature: &str) -> (&str, &str) {
+    match signature.rsplit_once('<') {
+        Some((name, email)) => (name.trim(), email.trim_end_matches('>').trim()),
+        None => (signature.trim(), ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(patterns: &[&str]) -> AuthorFilter {
+        AuthorFilter::new(&patterns.iter().map(|v| v.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    #[test]
+    fn exact_patterns_ignore_case() {
+        let filter = filter(&["Jane Doe", "john@example.com"]);
+        assert!(filter.matches("jane doe", "jane@example.com"));
+        assert!(filter.matches("John", "JOHN@example.com"));
+        assert!(!filter.matches("Jane", "jane.doe@example.com"));
+    }
+
+    #[test]
+    fn glob_patterns() {
+        let filter = filter(&["*@example.com", "glob:bot"]);
+        assert!(filter.matches("Jane", "jane@EXAMPLE.com"));
+        assert!(filter.matches("Bot", "ci@builds.local"));
+        assert!(!filter.matches("Jane", "jane@example.org"));
+        assert!(!filter.matches("robot", "robot@builds.local"));
+    }
+
+    #[test]
+    fn regex_patterns() {
+        let filter = filter(&["regex:^j(ane|ohn)$"]);
+        assert!(filter.matches("JANE", ""));
+        assert!(filter.matches("", "john"));
+        assert!(!filter.matches("janet", "jane@example.com"));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        assert!(AuthorFilter::new(&["regex:(".to_string()]).is_err());
+        assert!(AuthorFilter::new(&["glob:[".to_string()]).is_err());
+    }
+
+    #[test]
+    fn empty_filter() {
+        assert!(filter(&[]).is_empty());
+        assert!(!filter(&[]).matches("Jane", "jane@example.com"));
+    }
+
+    #[test]
+    fn splits_signatures() {
+        assert_eq!(split_signature("Jane Doe <jane@example.com>"), ("Jane Doe", "jane@example.com"));
+        assert_eq!(split_signature("Jane Doe"), ("Jane Doe", ""));
+    }
+}
@@ -0,0 +1,277 @@
+use {
+    crate::{default_true, event_end, CompressedGitEvent, DatabaseCommit},
+    serde::{Deserialize, Serialize},
+    server_api::{
+        db::Event,
+        external::types::{
+            api::CompressedEvent,
+            external::{
+                chrono::{DateTime, Duration, Utc},
+                serde_json,
+            },
+            timing::{TimeRange, Timing},
+        },
+    },
+    std::collections::HashMap,
+};
+
+/// Collapses bursts of commits in one repository into a single event.
+#[derive(Deserialize, Clone)]
+pub struct CompressionConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// How many commits a burst needs before it is collapsed.
+    #[serde(default = "default_min_commits")]
+    pub min_commits: usize,
+    /// Commits within this window, measured from the first commit of a burst,
+    /// belong to the same burst.
+    #[serde(default = "default_window_minutes")]
+    pub window_minutes: i64,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            enabled: true,
+            min_commits: default_min_commits(),
+            window_minutes: default_window_minutes(),
+        }
+    }
+}
+
+fn default_min_commits() -> usize {
+    5
+}
+
+fn default_window_minutes() -> i64 {
+    60
+}
+
+impl CompressionConfig {
+    fn window(&self) -> Duration {
+        Duration::try_minutes(self.window_minutes).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CommitGroup {
+    pub repository_name: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub commits: Vec<DatabaseCommit>,
+}
+
+type TimedCommit = (DateTime<Utc>, Event<DatabaseCommit>);
+
+/// Returns one event per commit, except for bursts of at least `min_commits`
+/// commits of one repository, which are returned as a single [`CommitGroup`].
+pub fn compress_commits(
+    commits: Vec<Event<DatabaseCommit>>,
+    config: &CompressionConfig,
+) -> Vec<CompressedEvent> {
+    let mut by_repository: HashMap<String, Vec<TimedCommit>> = HashMap::new();
+    for commit in commits {
+        by_repository
+            .entry(commit.event.repository_name.clone())
+            .or_default()
+            .push((event_end(&commit.timing), commit));
+    }
+
+    let mut result = Vec::new();
+    for (repository_name, mut commits) in by_repository {
+        commits.sort_by_key(|(time, _)| *time);
+
+        let mut bursts: Vec<Vec<TimedCommit>> = Vec::new();
+        for commit in commits {
+            match bursts.last_mut() {
+                Some(burst) if commit.0 - burst[0].0 <= config.window() => burst.push(commit),
+                _ => bursts.push(vec![commit]),
+            }
+        }
+
+        for burst in bursts {
+            if !config.enabled || burst.len() < config.min_commits.max(2) {
+                result.extend(burst.into_iter().map(|(_, v)| single_commit(v)));
+                continue;
+            }
+
+            let start = burst[0].0;
+            let end = burst[burst.len() - 1].0;
+            let group = CommitGroup {
+                repository_name: repository_name.clone(),
+                start,
+                end,
+                commits: burst.into_iter().map(|(_, v)| v.event).collect(),
+            };
+
+            result.push(CompressedEvent {
+                title: format!("{} commits in {}", group.commits.len(), repository_name),
+                time: match start == end {
+                    true => Timing::Instant(start),
+                    false => Timing::Range(TimeRange { start, end }),
+                },
+                data: serde_json::to_value(CompressedGitEvent::Group(group)).unwrap(),
+            });
+        }
+    }
+
+    result
+}
+
+pub fn single_commit(commit: Event<DatabaseCommit>) -> CompressedEvent {
+    CompressedEvent {
+        title: commit.event.repository_name.clone(),
+        time: commit.timing,
+        data: serde_json::to_value(CompressedGitEvent::Commit(Box::new(commit.event))).unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        server_api::external::types::{
+            available_plugins::AvailablePlugins,
+            external::{chrono::TimeZone, serde_json::json},
+        },
+    };
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+    }
+
+    fn commit(repository: &str, time: DateTime<Utc>) -> Event<DatabaseCommit> {
+        Event {
+            timing: Timing::Instant(time),
+            id: format!("{}:{}", repository, time.timestamp()),
+            plugin: AvailablePlugins::timeline_plugin_git,
+            event: serde_json::from_value(json!({
+                "message": "work",
+                "repository_name": repository,
+            }))
+            .unwrap(),
+        }
+    }
+
+    fn config(min_commits: usize) -> CompressionConfig {
+        CompressionConfig {
+            enabled: true,
+            min_commits,
+            window_minutes: 10,
+        }
+    }
+
+    /// Type, start, end and number of commits of an event.
+    type Summary = (String, DateTime<Utc>, DateTime<Utc>, usize);
+
+    fn summary(events: Vec<CompressedEvent>) -> Vec<Summary> {
+        let mut events: Vec<_> = events
+            .into_iter()
+            .map(|v| {
+                let (start, end) = match v.time {
+                    Timing::Instant(time) => (time, time),
+                    Timing::Range(range) => (range.start, range.end),
+                };
+                let commits = v.data["commits"].as_array().map_or(1, Vec::len);
+                (v.data["type"].as_str().unwrap().to_string(), start, end, commits)
+            })
+            .collect();
+        events.sort_by_key(|v| (v.1, v.2));
+        events
+    }
+
+    fn group(start: DateTime<Utc>, end: DateTime<Utc>, commits: usize) -> Summary {
+        ("group".to_string(), start, end, commits)
+    }
+
+    fn single(time: DateTime<Utc>) -> Summary {
+        ("commit".to_string(), time, time, 1)
+    }
+
+    #[test]
+    fn measures_the_window_from_the_first_commit() {
+        // Every commit is within the window of the previous one, but the
+        // fourth is outside the window of the first.
+        let commits = vec![
+            commit("app", at(10, 0)),
+            commit("app", at(10, 6)),
+            commit("app", at(10, 10)),
+            commit("app", at(10, 16)),
+            commit("app", at(10, 20)),
+        ];
+        assert_eq!(
+            summary(compress_commits(commits, &config(2))),
+            vec![group(at(10, 0), at(10, 10), 3), group(at(10, 16), at(10, 20), 2)]
+        );
+    }
+
+    #[test]
+    fn keeps_bursts_below_the_minimum_apart() {
+        let commits = vec![
+            commit("app", at(10, 0)),
+            commit("app", at(10, 5)),
+            commit("app", at(11, 0)),
+            commit("app", at(11, 1)),
+            commit("app", at(11, 2)),
+        ];
+        assert_eq!(
+            summary(compress_commits(commits, &config(3))),
+            vec![
+                single(at(10, 0)),
+                single(at(10, 5)),
+                group(at(11, 0), at(11, 2), 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn never_groups_a_single_commit() {
+        assert_eq!(
+            summary(compress_commits(vec![commit("app", at(10, 0))], &config(0))),
+            vec![single(at(10, 0))]
+        );
+        assert_eq!(
+            summary(compress_commits(vec![commit("app", at(10, 0))], &config(1))),
+            vec![single(at(10, 0))]
+        );
+    }
+
+    #[test]
+    fn returns_every_commit_when_disabled() {
+        let config = CompressionConfig {
+            enabled: false,
+            ..config(2)
+        };
+        let commits = vec![commit("app", at(10, 0)), commit("app", at(10, 1))];
+        assert_eq!(
+            summary(compress_commits(commits, &config)),
+            vec![single(at(10, 0)), single(at(10, 1))]
+        );
+    }
+
+    #[test]
+    fn groups_per_repository() {
+        let commits = vec![
+            commit("app", at(10, 0)),
+            commit("lib", at(10, 1)),
+            commit("app", at(10, 2)),
+        ];
+        assert_eq!(
+            summary(compress_commits(commits, &config(2))),
+            vec![group(at(10, 0), at(10, 2), 2), single(at(10, 1))]
+        );
+    }
+
+    #[test]
+    fn times_a_group_of_simultaneous_commits_as_an_instant() {
+        let commits = vec![commit("app", at(10, 0)), commit("app", at(10, 0))];
+        let events = compress_commits(commits, &config(2));
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].time, Timing::Instant(time) if time == at(10, 0)));
+
+        let commits = vec![commit("app", at(10, 0)), commit("app", at(10, 1))];
+        let events = compress_commits(commits, &config(2));
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0].time, Timing::Range(_)));
+    }
+}
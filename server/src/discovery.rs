@@ -0,0 +1,288 @@
+use {
+    globset::{Glob, GlobSet, GlobSetBuilder},
+    serde::Deserialize,
+    server_api::external::tokio::fs::{self, read_dir},
+    std::{
+        collections::HashSet,
+        path::{Path, PathBuf},
+    },
+};
+
+/// Directory names that never contain repositories worth tracking.
+const DEFAULT_IGNORED: &[&str] = &["node_modules", "target"];
+
+#[derive(Deserialize, Clone)]
+pub struct DiscoveryConfig {
+    /// How many directory levels below each root are searched. `1` only looks
+    /// at the direct children of a root.
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+    /// Glob patterns for directories to skip. They are matched against both the
+    /// directory name and its full path.
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        DiscoveryConfig {
+            max_depth: default_max_depth(),
+            ignore: Vec::new(),
+        }
+    }
+}
+
+fn default_max_depth() -> usize {
+    3
+}
+
+impl DiscoveryConfig {
+    pub fn build_ignore_set(&self) -> Result<GlobSet, globset::Error> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in DEFAULT_IGNORED
+            .iter()
+            .copied()
+            .chain(self.ignore.iter().map(|v| v.as_str()))
+        {
+            builder.add(Glob::new(pattern)?);
+        }
+        builder.build()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DiscoveredRepository {
+    pub path: PathBuf,
+    /// Path relative to the root it was found in, which keeps equally named
+    /// checkouts in different folders apart.
+    pub name: String,
+}
+
+/// Searches every root for git repositories. Working trees are searched as well,
+/// so submodules and nested worktrees are found too. Bare repositories are not
+/// descended into.
+///
+/// A root that can not be read, such as an unmounted drive, does not stop the
+/// others from being searched. It is returned as a warning instead.
+///
+/// Overlapping roots find some repositories more than once. Every repository is
+/// only returned for the first root that found it.
+pub async fn discover_repositories(
+    roots: &[PathBuf],
+    max_depth: usize,
+    ignore: &GlobSet,
+) -> (Vec<DiscoveredRepository>, Vec<String>) {
+    let mut repositories = Vec::new();
+    let mut warnings = Vec::new();
+    let mut found = HashSet::new();
+
+    for root in roots {
+        if let Err(e) = fs::metadata(root).await {
+            warnings.push(format!(
+                "Unable to read repositories directory: \nPath: {} \nError: {}",
+                root.display(),
+                e
+            ));
+            continue;
+        }
+
+        let mut pending = vec![(root.clone(), 0)];
+
+        while let Some((dir, depth)) = pending.pop() {
+            let bare = is_bare_repository(&dir).await;
+            let canonical = fs::canonicalize(&dir).await.unwrap_or_else(|_| dir.clone());
+            if (bare || is_repository(&dir).await) && found.insert(canonical) {
+                repositories.push(DiscoveredRepository {
+                    name: repository_name(root, &dir),
+                    path: dir.clone(),
+                });
+            }
+            if bare {
+                continue;
+            }
+
+            if depth >= max_depth {
+                continue;
+            }
+
+            // Unreadable subdirectories are skipped instead of failing the whole discovery.
+            let Ok(mut entries) = read_dir(&dir).await else {
+                continue;
+            };
+
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                match entry.file_type().await {
+                    Ok(v) if v.is_dir() => {}
+                    _ => continue,
+                }
+
+                let path = entry.path();
+                if entry.file_name() == ".git"
+                    || ignore.is_match(entry.file_name())
+                    || ignore.is_match(&path)
+                {
+                    continue;
+                }
+
+                pending.push((path, depth + 1));
+            }
+        }
+    }
+
+    (repositories, warnings)
+}
+
+/// A working tree has a `.git` directory, or a `.git` file for worktrees and submodules.
+async fn is_repository(path: &Path) -> bool {
+    fs::metadata(path.join(".git")).await.is_ok()
+}
+
+/// Bare repositories and mirrors (`project.git`) have no working tree, their
+/// `HEAD`, `objects` and `refs` live directly in the directory.
+async fn is_bare_repository(path: &Path) -> bool {
+    let (head, objects, refs) = (
+        fs::metadata(path.join("HEAD")).await,
+        fs::metadata(path.join("objects")).await,
+        fs::metadata(path.join("refs")).await,
+    );
+    matches!(
+        (head, objects, refs),
+        (Ok(head), Ok(objects), Ok(refs)) if head.is_file() && objects.is_dir() && refs.is_dir()
+    )
+}
+
+/// Drops the `.git` suffix bare repositories are usually named with.
+pub fn strip_git_suffix(name: &str) -> &str {
+    match name.strip_suffix(".git") {
+        Some(v) if !v.is_empty() => v,
+        _ => name,
+    }
+}
+
+fn repository_name(root: &Path, path: &Path) -> String {
+    let name = match path.strip_prefix(root) {
+        Ok(v) if !v.as_os_str().is_empty() => v
+            .components()
+            .map(|v| v.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        _ => path
+            .file_name()
+            .unwrap_or(path.as_os_str())
+            .to_string_lossy()
+            .to_string(),
+    };
+
+    strip_git_suffix(&name).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, server_api::external::tokio::runtime::Runtime};
+
+    /// A scratch directory that is removed again when the test ends.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("timeline_plugin_git_{}_{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Scratch(path)
+        }
+
+        fn working_tree(&self, path: &str) {
+            std::fs::create_dir_all(self.0.join(path).join(".git")).unwrap();
+        }
+
+        fn bare(&self, path: &str) {
+            let path = self.0.join(path);
+            std::fs::create_dir_all(path.join("objects")).unwrap();
+            std::fs::create_dir_all(path.join("refs")).unwrap();
+            std::fs::write(path.join("HEAD"), "ref: refs/heads/main\n").unwrap();
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn discover(roots: &[&Path], max_depth: usize, ignore: &[&str]) -> Vec<String> {
+        let roots: Vec<PathBuf> = roots.iter().map(|v| v.to_path_buf()).collect();
+        let ignore = DiscoveryConfig {
+            max_depth,
+            ignore: ignore.iter().map(|v| v.to_string()).collect(),
+        }
+        .build_ignore_set()
+        .unwrap();
+        let (repositories, warnings) = Runtime::new()
+            .unwrap()
+            .block_on(discover_repositories(&roots, max_depth, &ignore));
+        assert!(warnings.is_empty());
+
+        let mut names: Vec<String> = repositories.into_iter().map(|v| v.name).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn names_repositories_by_their_path_below_the_root() {
+        let scratch = Scratch::new("names");
+        scratch.working_tree("app");
+        scratch.working_tree("org/app");
+        scratch.bare("mirrors/tool.git");
+
+        assert_eq!(
+            discover(&[&scratch.0], 3, &[]),
+            vec!["app", "mirrors/tool", "org/app"]
+        );
+    }
+
+    #[test]
+    fn stops_at_the_maximum_depth() {
+        let scratch = Scratch::new("depth");
+        scratch.working_tree("a");
+        scratch.working_tree("b/c");
+        scratch.working_tree("d/e/f");
+
+        assert_eq!(discover(&[&scratch.0], 1, &[]), vec!["a"]);
+        assert_eq!(discover(&[&scratch.0], 2, &[]), vec!["a", "b/c"]);
+    }
+
+    #[test]
+    fn skips_ignored_directories() {
+        let scratch = Scratch::new("ignore");
+        scratch.working_tree("app");
+        scratch.working_tree("app/node_modules/dependency");
+        scratch.working_tree("archive/old");
+        scratch.working_tree("vendor/lib");
+
+        assert_eq!(discover(&[&scratch.0], 3, &["archive", "**/vendor"]), vec!["app"]);
+    }
+
+    #[test]
+    fn finds_repositories_of_overlapping_roots_once() {
+        let scratch = Scratch::new("overlap");
+        scratch.working_tree("org/app");
+
+        assert_eq!(
+            discover(&[&scratch.0, &scratch.0.join("org"), &scratch.0], 3, &[]),
+            vec!["org/app"]
+        );
+    }
+
+    #[test]
+    fn reports_missing_roots() {
+        let ignore = DiscoveryConfig::default().build_ignore_set().unwrap();
+        let (repositories, warnings) = Runtime::new().unwrap().block_on(discover_repositories(
+            &[PathBuf::from("/nonexistent/timeline_plugin_git")],
+            3,
+            &ignore,
+        ));
+        assert!(repositories.is_empty());
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -1,17 +1,341 @@
+mod authors;
+mod compression;
+mod discovery;
+mod message;
+mod references;
+mod reflog;
+mod rewrites;
+mod schedule;
+mod sessions;
+mod tags;
+mod watcher;
+
 use {
-    git2::Repository, serde::{Deserialize, Serialize}, server_api::{
-        db::{Database, Event}, external::{futures::{self, StreamExt, TryStreamExt}, tokio::fs::read_dir, types::{self, api::CompressedEvent, available_plugins::AvailablePlugins, external::{chrono::{self, DateTime, Duration, FixedOffset, TimeZone, Utc}, mongodb::bson::doc, serde_json}, timing::Timing}}, plugin::{PluginData, PluginTrait}
-    }, std::{path::{Path, PathBuf}, thread}
+    authors::{split_signature, AuthorFilter}, compression::{compress_commits, single_commit, CommitGroup, CompressionConfig}, discovery::{discover_repositories, strip_git_suffix, DiscoveryConfig}, message::{decode_message, parse_message, ParsedMessage}, references::{IssueReference, ReferenceExtractor, ReferencePattern}, reflog::{local_branch_tips, read_reflogs, DatabaseReflogEntry, ReflogAction, ReflogEntry}, rewrites::{patch_id, reachable_commits, RewritePolicy}, schedule::{PollingConfig, RepositorySchedule}, git2::Repository, globset::GlobSet, sessions::{build_sessions, SessionConfig, WorkSession}, tags::{is_tagged_by, read_tags, DatabaseTag, Tag}, watcher::{RepositoryWatcher, WatchConfig}, serde::{Deserialize, Serialize}, server_api::{
+        db::{Database, Event}, external::{futures::{self, StreamExt, TryStreamExt}, tokio::{fs, sync::Mutex, task}, types::{self, api::CompressedEvent, available_plugins::AvailablePlugins, external::{chrono::{self, DateTime, Duration, FixedOffset, TimeZone, Utc}, mongodb::{bson::{self, doc, Document}, error::ErrorKind, options::{FindOptions, IndexOptions, InsertManyOptions, ReplaceOptions}, IndexModel}, serde_json}, timing::Timing}}, plugin::{PluginData, PluginTrait}
+    }, std::{collections::{BTreeMap, HashMap, HashSet}, path::{Path, PathBuf}, sync::Arc}
 };
 
 #[derive(Deserialize)]
 struct ConfigData {
-    pub repo_folder: PathBuf,
+    /// Single root folder, kept for configs written before `repo_folders` existed.
+    #[serde(default)]
+    pub repo_folder: Option<PathBuf>,
+    #[serde(default)]
+    pub repo_folders: Vec<PathBuf>,
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+    #[serde(default)]
+    pub refs: RefSelection,
+    /// Identities whose commits are imported, see [`AuthorFilter`] for the
+    /// pattern syntax. A commit is kept if its author or committer matches.
+    /// Empty imports everyone.
+    #[serde(default)]
+    pub authors: Vec<String>,
+    /// Issue and pull request references that are extracted from commit messages.
+    #[serde(default)]
+    pub references: Vec<ReferencePattern>,
+    /// Resolve identities through the repository's `.mailmap` before filtering.
+    #[serde(default = "default_true")]
+    pub mailmap: bool,
+    #[serde(default)]
+    pub diff_stats: DiffStatsConfig,
+    /// Shows work sessions spanning consecutive commits instead of single commits.
+    #[serde(default)]
+    pub sessions: Option<SessionConfig>,
+    /// Which time of a commit its event is placed at.
+    #[serde(default)]
+    pub timestamp_source: TimestampSource,
+    /// Show tags as their own events. With an author filter, only tags whose
+    /// tagger, or for lightweight tags whose commit, matches it are shown.
+    #[serde(default = "default_true")]
+    pub track_tags: bool,
+    /// Show checkouts, pulls, rebases, resets and branch changes from the reflog,
+    /// with the time they actually happened locally.
+    #[serde(default)]
+    pub reflog: bool,
+    /// Only applies when sessions are off, sessions already merge bursts of commits.
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    /// What happens to stored commits that vanished from the history.
+    #[serde(default)]
+    pub rewrites: RewritePolicy,
+    /// Repositories that are tracked in addition to the discovered ones. An entry
+    /// with the same path as a discovered repository overrides its options.
+    #[serde(default)]
+    pub repositories: Vec<RepositoryConfig>,
+    /// How many repositories are scanned at the same time.
+    #[serde(default = "default_parallelism")]
+    pub parallelism: usize,
+    #[serde(default)]
+    pub polling: PollingConfig,
+    /// Scans repositories right after their refs changed. The regular scan keeps
+    /// running as a fallback for changes the watcher missed.
+    #[serde(default)]
+    pub watch: WatchConfig,
+    /// Commits, tags and reflog entries from before this time are not imported,
+    /// e.g. `"2020-01-01T00:00:00Z"`.
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// Commits, tags and reflog entries from after this time are not imported.
+    /// Raising it later imports what was left out.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// Only the newest commits of a repository are imported when it is scanned
+    /// for the first time. Later scans import every new commit.
+    #[serde(default)]
+    pub max_commits_per_repo: Option<usize>,
+    /// Imports the history between this time and `since` once, without the
+    /// `max_commits_per_repo` limit. Set it to an earlier time to backfill again.
+    #[serde(default)]
+    pub backfill_since: Option<DateTime<Utc>>,
+    /// Where the scan state was kept before it moved into the database. It is
+    /// read once if the database holds no state yet.
+    #[serde(default)]
+    pub state_file: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+enum TimestampSource {
+    /// When the change was originally written, which survives rebases.
+    Author,
+    /// When the commit was last created or rewritten.
+    #[default]
+    Committer,
+    /// A range from author time to commit time whenever the two differ.
+    Both,
+}
+
+/// Limits for the per-commit diff against the first parent.
+#[derive(Deserialize, Clone)]
+struct DiffStatsConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Commits touching more files only record the number of changed files,
+    /// counting lines of huge diffs is too expensive.
+    #[serde(default = "default_max_diff_files")]
+    pub max_files: usize,
+    /// How many touched paths are stored per commit.
+    #[serde(default = "default_max_diff_paths")]
+    pub max_paths: usize,
+}
+
+impl Default for DiffStatsConfig {
+    fn default() -> Self {
+        DiffStatsConfig {
+            enabled: true,
+            max_files: default_max_diff_files(),
+            max_paths: default_max_diff_paths(),
+        }
+    }
+}
+
+fn default_max_diff_files() -> usize {
+    1000
+}
+
+fn default_max_diff_paths() -> usize {
+    50
+}
+
+/// Which references of a repository are walked when collecting commits.
+#[derive(Deserialize, Clone)]
+struct RefSelection {
+    /// Include whatever HEAD points to, even if it is detached.
+    #[serde(default = "default_true")]
+    pub head: bool,
+    /// Include all local branches (`refs/heads/*`).
+    #[serde(default = "default_true")]
+    pub local_branches: bool,
+    /// Include remote-tracking branches (`refs/remotes/*`). Nobody commits to a
+    /// bare repository directly, so they are always included there, except in
+    /// mirrors where `refs/heads` already holds the upstream branches.
+    #[serde(default)]
+    pub remote_branches: bool,
+    /// Include tags (`refs/tags/*`).
+    #[serde(default)]
+    pub tags: bool,
+    /// Additional reference globs, e.g. `refs/heads/release/*`.
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+impl Default for RefSelection {
+    fn default() -> Self {
+        RefSelection {
+            head: true,
+            local_branches: true,
+            remote_branches: false,
+            tags: false,
+            patterns: Vec::new(),
+        }
+    }
+}
+
+impl RefSelection {
+    fn globs(&self) -> Vec<&str> {
+        let mut globs = Vec::new();
+        if self.local_branches {
+            globs.push("refs/heads/*");
+        }
+        if self.remote_branches {
+            globs.push("refs/remotes/*");
+        }
+        if self.tags {
+            globs.push("refs/tags/*");
+        }
+        globs.extend(self.patterns.iter().map(|v| v.as_str()));
+        globs
+    }
+}
+
+#[derive(Deserialize, Clone)]
+struct RepositoryConfig {
+    pub path: PathBuf,
+    /// Display name, defaults to the directory derived name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Replaces the global `refs` selection for this repository.
+    #[serde(default)]
+    pub refs: Option<RefSelection>,
+    /// Replaces the global `authors` filter for this repository. An empty list
+    /// imports everyone's commits.
+    #[serde(default)]
+    pub authors: Option<Vec<String>>,
+    /// Replaces the global `references` patterns for this repository.
+    #[serde(default)]
+    pub references: Option<Vec<ReferencePattern>>,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+/// A repository with all options resolved, ready to be scanned.
+#[derive(Clone)]
+struct RepositoryTarget {
+    pub path: PathBuf,
+    pub name: String,
+    pub refs: RefSelection,
+    pub authors: AuthorFilter,
+    pub references: ReferenceExtractor,
+}
+
+impl ConfigData {
+    fn window(&self) -> ScanWindow {
+        ScanWindow {
+            since: self.since,
+            until: self.until,
+            max_commits: self.max_commits_per_repo,
+            full: false,
+        }
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.repo_folder
+            .iter()
+            .chain(self.repo_folders.iter())
+            .cloned()
+            .collect()
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_parallelism() -> usize {
+    std::thread::available_parallelism()
+        .map(|v| v.get())
+        .unwrap_or(4)
 }
 
 pub struct Plugin {
     plugin_data: PluginData,
     config: ConfigData,
+    ignore: GlobSet,
+    authors: AuthorFilter,
+    /// Canonical repository path -> its own author filter.
+    repository_authors: HashMap<PathBuf, AuthorFilter>,
+    references: ReferenceExtractor,
+    /// Repository name -> its own reference patterns.
+    repository_references: HashMap<String, ReferenceExtractor>,
+    state: Mutex<ScanState>,
+    watcher: Option<Arc<RepositoryWatcher>>,
+    /// Targets of the last scheduled scan, reused for scans triggered by the watcher.
+    targets: Mutex<Vec<RepositoryTarget>>,
+    last_scheduled_scan: Mutex<Option<DateTime<Utc>>>,
+    schedule: Mutex<HashMap<PathBuf, RepositorySchedule>>,
+}
+
+/// Scan progress, stored in the database so it survives restarts.
+#[derive(Serialize, Deserialize, Default)]
+struct ScanState {
+    /// Repository path -> full reference name -> last tip that was fully imported.
+    #[serde(default)]
+    watermarks: HashMap<String, HashMap<String, String>>,
+    /// The author filter configuration that stored commits were filtered with.
+    #[serde(default)]
+    author_filter: Option<String>,
+    /// The timestamp source stored commits were timed with.
+    #[serde(default)]
+    timestamp_source: Option<String>,
+    /// Repository path -> reference name -> time of the newest reflog entry that was read.
+    #[serde(default)]
+    reflog_marks: HashMap<String, HashMap<String, i64>>,
+    /// Repository path -> local branch -> tip, to notice deleted branches.
+    #[serde(default)]
+    local_branches: HashMap<String, HashMap<String, String>>,
+    /// Repository path -> tag -> commit it pointed at during the last scan.
+    #[serde(default)]
+    tags: HashMap<String, HashMap<String, String>>,
+    /// The `until` that the watermarks were set with.
+    #[serde(default)]
+    until: Option<DateTime<Utc>>,
+    /// The `backfill_since` of the last completed backfill.
+    #[serde(default)]
+    backfill_since: Option<DateTime<Utc>>,
+}
+
+/// Which part of the history a scan imports.
+#[derive(Clone, Copy)]
+struct ScanWindow {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    /// Per scan of a repository.
+    max_commits: Option<usize>,
+    /// Walks every reference from its tip, ignoring the watermarks, and leaves
+    /// the scan state untouched. Used for backfills.
+    full: bool,
+}
+
+impl ScanWindow {
+    fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.since.is_none_or(|v| time >= v) && self.until.is_none_or(|v| time <= v)
+    }
+}
+
+/// Everything one scan of a repository produced.
+#[derive(Default)]
+struct ScanResult {
+    commits: Vec<Commit>,
+    /// Problems that did not stop the scan, such as undecodable commit messages.
+    warnings: Vec<String>,
+    /// Tags that are new or point at another commit than before.
+    tags: Vec<Tag>,
+    /// Every tag of the repository and the commit it points at.
+    tag_targets: HashMap<String, String>,
+    reflog: Vec<ReflogEntry>,
+    /// Only present if the tips changed since the last scan.
+    watermarks: Option<HashMap<String, String>>,
+    reflog_marks: HashMap<String, i64>,
+    local_branches: HashMap<String, String>,
+    /// Commit time of the newest selected tip.
+    last_activity: Option<DateTime<Utc>>,
+    /// All commits reachable from the selected references. Only collected if
+    /// history was rewritten and stored commits may have vanished.
+    reachable: Option<HashSet<git2::Oid>>,
+    /// Reference -> ids of stored commits that it reaches since this scan.
+    contained: HashMap<String, Vec<String>>,
 }
 
 impl PluginTrait for Plugin {
@@ -31,9 +355,157 @@ impl PluginTrait for Plugin {
             )
         });
 
+        let ignore = config.discovery.build_ignore_set().unwrap_or_else(|e| {
+            panic!(
+                "Unable to init git plugin! Provided ignore patterns are invalid: {}",
+                e
+            )
+        });
+
+        let authors = AuthorFilter::new(&config.authors).unwrap_or_else(|e| {
+            panic!(
+                "Unable to init git plugin! Provided author filter is invalid: {}",
+                e
+            )
+        });
+
+        let repository_authors = config
+            .repositories
+            .iter()
+            .filter_map(|repository| {
+                let patterns = repository.authors.as_ref()?;
+                let filter = AuthorFilter::new(patterns).unwrap_or_else(|e| {
+                    panic!(
+                        "Unable to init git plugin! Provided author filter of {} is invalid: {}",
+                        repository.path.display(),
+                        e
+                    )
+                });
+                Some((canonical_path(&repository.path), filter))
+            })
+            .collect();
+
+        let references = ReferenceExtractor::new(&config.references).unwrap_or_else(|e| {
+            panic!(
+                "Unable to init git plugin! Provided reference pattern is invalid: {}",
+                e
+            )
+        });
+
+        let repository_references = config
+            .repositories
+            .iter()
+            .filter_map(|repository| {
+                let patterns = repository.references.as_ref()?;
+                let name = repository_name(&canonical_path(&repository.path), repository)
+                    .unwrap_or_else(|e| panic!("Unable to init git plugin! {}", e));
+                let extractor = ReferenceExtractor::new(patterns).unwrap_or_else(|e| {
+                    panic!(
+                        "Unable to init git plugin! Provided reference pattern of {} is invalid: {}",
+                        name, e
+                    )
+                });
+                Some((name, extractor))
+            })
+            .collect();
+
+        if let Err(e) = config.polling.validate() {
+            panic!(
+                "Unable to init git plugin! Provided polling configuration is invalid: {}",
+                e
+            )
+        }
+
+        let state = match load_state(&data.database).await {
+            Ok(Some(v)) => v,
+            Ok(None) => match &config.state_file {
+                Some(path) => match fs::read(path).await {
+                    Ok(v) => serde_json::from_slice(&v).unwrap_or_else(|e| {
+                        data.report_error_string(format!(
+                            "Unable to parse git plugin state, starting with full rescans: {}",
+                            e
+                        ));
+                        ScanState::default()
+                    }),
+                    Err(_) => ScanState::default(),
+                },
+                None => ScanState::default(),
+            },
+            Err(e) => {
+                data.report_error_string(format!(
+                    "Unable to load git plugin state, starting with full rescans: {}",
+                    e
+                ));
+                ScanState::default()
+            }
+        };
+
+        // Commits were stored without a kind before tags were tracked as well.
+        if let Err(e) = data
+            .database
+            .get_events::<DatabaseGitEvent>()
+            .update_many(
+                Database::combine_documents(
+                    Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_git),
+                    doc! {
+                        "event.kind": {
+                            "$exists": false
+                        }
+                    },
+                ),
+                doc! {
+                    "$set": {
+                        "event.kind": "commit"
+                    }
+                },
+                None,
+            )
+            .await
+        {
+            data.report_error_string(format!("Unable to migrate stored commits: {}", e));
+        }
+
+        // Configured names are used as they are, only derived ones lost their suffix.
+        let configured_names: Vec<&str> = config
+            .repositories
+            .iter()
+            .filter_map(|v| v.name.as_deref())
+            .collect();
+        if let Err(e) = strip_stored_git_suffixes(&data.database, &configured_names).await {
+            data.report_error_string(format!("Unable to migrate stored repository names: {}", e));
+        }
+
+        if let Err(e) = ensure_commit_identity(&data.database).await {
+            data.report_error_string(format!("Unable to migrate stored commit ids: {}", e));
+        }
+
+        let watcher = match config.watch.enabled {
+            true => match RepositoryWatcher::new() {
+                Ok(v) => Some(Arc::new(v)),
+                Err(e) => {
+                    data.report_error_string(format!(
+                        "Unable to start repository watcher, falling back to polling: {}",
+                        e
+                    ));
+                    None
+                }
+            },
+            false => None,
+        };
+
         Plugin {
             plugin_data: data,
             config,
+            ignore,
+            authors,
+            repository_authors,
+            references,
+            repository_references,
+            state: Mutex::new(state),
+            watcher,
+            targets: Mutex::new(Vec::new()),
+            last_scheduled_scan: Mutex::new(None),
+            schedule: Mutex::new(HashMap::new()),
         }
     }
 
@@ -49,12 +521,44 @@ impl PluginTrait for Plugin {
     ) -> std::pin::Pin<Box<dyn futures::Future<Output = Option<chrono::Duration>> + Send + 'a>>
     {
         Box::pin(async move {
-            if let Err(e) = self.update_commits().await {
-                self.plugin_data
-                    .report_error_string(format!("Unable to refresh recent commits: {}", e))
+            let tick = self.config.polling.tick();
+            let changed = match &self.watcher {
+                Some(watcher) => watcher.take_settled(self.config.watch.debounce()),
+                None => Vec::new(),
+            };
+
+            let scheduled_scan_due = self
+                .last_scheduled_scan
+                .lock()
+                .await
+                .is_none_or(|v| Utc::now() - v >= tick);
+
+            if scheduled_scan_due {
+                *self.last_scheduled_scan.lock().await = Some(Utc::now());
+                if let Err(e) = self.update_commits(&changed).await {
+                    self.plugin_data
+                        .report_error_string(format!("Unable to refresh recent commits: {}", e))
+                }
+            } else if !changed.is_empty() {
+                let targets: Vec<RepositoryTarget> = self
+                    .targets
+                    .lock()
+                    .await
+                    .iter()
+                    .filter(|v| changed.contains(&v.path))
+                    .cloned()
+                    .collect();
+                self.scan_targets(targets, self.config.window()).await;
             }
 
-            Some(Duration::try_minutes(15).unwrap())
+            match &self.watcher {
+                Some(_) => Some(
+                    Duration::from_std(self.config.watch.debounce())
+                        .unwrap_or(tick)
+                        .max(Duration::try_seconds(1).unwrap()),
+                ),
+                None => Some(tick),
+            }
         })
     }
 
@@ -67,24 +571,128 @@ impl PluginTrait for Plugin {
                 + Send,
         >,
     > {
-        let filter = Database::combine_documents(
-            Database::generate_find_plugin_filter(Plugin::get_type()),
-            Database::generate_range_filter(query_range)
+        let range = types::timing::TimeRange {
+            start: query_range.start,
+            end: query_range.end,
+        };
+        let sessions = self.config.sessions.clone();
+        let compression = self.config.compression.clone();
+        let tag_filter = Database::combine_documents(
+            kind_filter("tag"),
+            Database::generate_range_filter(&range),
         );
+        let reflog_filter = Database::combine_documents(
+            kind_filter("reflog"),
+            Database::generate_range_filter(&range),
+        );
+        // Sessions crossing the borders of the range need their commits from outside of it.
+        let commit_range = match &sessions {
+            Some(v) => types::timing::TimeRange {
+                start: range.start - v.max_gap() - v.lead_in(),
+                end: range.end + v.max_gap(),
+            },
+            None => types::timing::TimeRange {
+                start: range.start,
+                end: range.end,
+            },
+        };
         let database = self.plugin_data.database.clone();
+        let references = self.references.clone();
+        let repository_references = self.repository_references.clone();
         Box::pin(async move {
+            let mut commits = Vec::new();
+            let mut loaded = HashSet::new();
+            let (mut loaded_start, mut loaded_end) = (commit_range.start, commit_range.end);
+            let (mut earliest, mut latest): (Option<DateTime<Utc>>, Option<DateTime<Utc>>) =
+                (None, None);
+            let mut commit_range = commit_range;
+            loop {
+                let filter = Database::combine_documents(
+                    kind_filter("commit"),
+                    Database::generate_range_filter(&commit_range),
+                );
+                let mut cursor = database
+                    .get_events::<DatabaseCommit>()
+                    .find(filter, None)
+                    .await?;
+                while let Some(v) = cursor.next().await {
+                    let mut t = v?;
+                    if !loaded.insert(t.id.clone()) {
+                        continue;
+                    }
+                    let time = event_end(&t.timing);
+                    earliest = Some(earliest.map_or(time, |v| v.min(time)));
+                    latest = Some(latest.map_or(time, |v| v.max(time)));
+                    t.event.upgrade_legacy_identity();
+                    t.event.upgrade_legacy_message();
+                    // Extracted again, so changed patterns apply to stored commits as well.
+                    let extractor = repository_references
+                        .get(&t.event.repository_name)
+                        .unwrap_or(&references);
+                    t.event.references = extractor.extract(&t.event.message);
+                    extractor.link(&mut t.event.references, &t.event.repository_name);
+                    commits.push(t);
+                }
+
+                // A session reaching into the range can have started long
+                // before it or go on long after it. More commits are loaded on
+                // either side until there is a gap that no session spans, so
+                // every range sees a session the same way.
+                let Some(max_gap) = sessions.as_ref().map(|v| v.max_gap()) else {
+                    break;
+                };
+                commit_range = if earliest.is_some_and(|v| v - loaded_start < max_gap) {
+                    loaded_start -= max_gap;
+                    types::timing::TimeRange {
+                        start: loaded_start,
+                        end: loaded_start + max_gap,
+                    }
+                } else if latest.is_some_and(|v| loaded_end - v < max_gap) {
+                    loaded_end += max_gap;
+                    types::timing::TimeRange {
+                        start: loaded_end - max_gap,
+                        end: loaded_end,
+                    }
+                } else {
+                    break;
+                };
+            }
+
+            // Rewritten commits stay on their own, they are not part of the
+            // history anymore that sessions and groups summarise.
+            let (rewritten, commits): (Vec<_>, Vec<_>) =
+                commits.into_iter().partition(|v| v.event.rewritten);
+
+            let mut result = match sessions {
+                Some(sessions) => build_sessions(commits, &sessions, &range),
+                None => compress_commits(commits, &compression),
+            };
+            result.extend(rewritten.into_iter().map(single_commit));
+
             let mut cursor = database
-                .get_events::<DatabaseCommit>()
-                .find(filter, None)
+                .get_events::<DatabaseTag>()
+                .find(tag_filter, None)
+                .await?;
+            while let Some(v) = cursor.next().await {
+                let t = v?;
+                result.push(CompressedEvent {
+                    title: format!("{} {}", t.event.repository_name, t.event.tag_name),
+                    time: t.timing,
+                    data: serde_json::to_value(CompressedGitEvent::Tag(t.event)).unwrap(),
+                });
+            }
+
+            let mut cursor = database
+                .get_events::<DatabaseReflogEntry>()
+                .find(reflog_filter, None)
                 .await?;
-            let mut result = Vec::new();
             while let Some(v) = cursor.next().await {
                 let t = v?;
                 result.push(CompressedEvent {
                     title: t.event.repository_name.clone(),
                     time: t.timing,
-                    data: serde_json::to_value(t.event).unwrap(),
-                })
+                    data: serde_json::to_value(CompressedGitEvent::Reflog(t.event)).unwrap(),
+                });
             }
 
             Ok(result)
@@ -93,53 +701,275 @@ impl PluginTrait for Plugin {
 }
 
 impl Plugin {
-    async fn update_commits(&self) -> Result<(), String> {
-        let mut dirs = match read_dir(&self.config.repo_folder).await {
-            Ok(v) => v,
-            Err(e) => return Err(format!("Unable to read repositories directory: {}", e)),
-        };
+    /// Scans every repository that is due, plus the `changed` ones reported by the watcher.
+    async fn update_commits(&self, changed: &[PathBuf]) -> Result<(), String> {
+        let mut targets = self.resolve_targets().await?;
 
-        while let Some(entry) = match dirs.next_entry().await {
-            Ok(v) => v,
-            Err(e) => {
-                return Err(format!("Unable to read repositories directory: {}", e));
-            }
-        } {
-            let entry_type = match entry.file_type().await {
-                Ok(v) => v,
-                Err(e) => {
-                    return Err(format!(
-                        "Unable to check directory entry for file-type: {}",
-                        e
-                    ));
+        if let Some(watcher) = &self.watcher {
+            let paths: Vec<PathBuf> = targets.iter().map(|v| v.path.clone()).collect();
+            let watcher = watcher.clone();
+            match task::spawn_blocking(move || watcher.watch(&paths)).await {
+                Ok(errors) => {
+                    for e in errors {
+                        self.plugin_data.report_error_string(e);
+                    }
                 }
-            };
+                Err(e) => self
+                    .plugin_data
+                    .report_error_string(format!("Unable to join watching task: {}", e)),
+            }
+            *self.targets.lock().await = targets.clone();
+        }
+
+        let author_filter = format!(
+            "{:?} {} {:?}",
+            self.config.authors,
+            self.config.mailmap,
+            self.config
+                .repositories
+                .iter()
+                .map(|v| (&v.path, &v.authors))
+                .collect::<Vec<_>>()
+        );
+        if self.state.lock().await.author_filter.as_ref() != Some(&author_filter) {
+            self.refilter_existing_commits(&targets).await?;
+
+            // Commits the previous filter skipped have to be picked up again.
+            // The repositories stay known, so `max_commits_per_repo` is not applied again.
+            let mut state = self.state.lock().await;
+            state.watermarks.values_mut().for_each(HashMap::clear);
+            state.tags.clear();
+            state.author_filter = Some(author_filter);
+            self.save_state(&state).await?;
+        }
+
+        // Commits and tags after the previous `until` were walked past, they
+        // are only found again by walking every reference from its tip.
+        if self.state.lock().await.until != self.config.until {
+            let mut state = self.state.lock().await;
+            if state.until.is_some_and(|v| self.config.until.is_none_or(|until| until > v)) {
+                state.watermarks.values_mut().for_each(HashMap::clear);
+                state.tags.clear();
+            }
+            state.until = self.config.until;
+            self.save_state(&state).await?;
+        }
+
+        let timestamp_source = format!("{:?}", self.config.timestamp_source);
+        if self.state.lock().await.timestamp_source.as_ref() != Some(&timestamp_source) {
+            self.retime_existing_commits().await?;
+
+            let mut state = self.state.lock().await;
+            state.timestamp_source = Some(timestamp_source);
+            self.save_state(&state).await?;
+        }
+
+        if let Some(backfill_since) = self.config.backfill_since {
+            if self.state.lock().await.backfill_since != Some(backfill_since) {
+                let window = ScanWindow {
+                    since: Some(backfill_since),
+                    until: self.config.since.or(self.config.until),
+                    max_commits: None,
+                    full: true,
+                };
 
-            if entry_type.is_dir() {
-                self.get_commits_in_repo(&entry.path()).await?;
+                // Repositories that failed are backfilled by the next attempt.
+                if self.scan_targets(targets, window).await {
+                    let mut state = self.state.lock().await;
+                    state.backfill_since = Some(backfill_since);
+                    self.save_state(&state).await?;
+                }
+                return Ok(());
             }
         }
 
+        if self.config.polling.adaptive {
+            let now = Utc::now();
+            let schedule = self.schedule.lock().await;
+            targets.retain(|target| {
+                changed.contains(&target.path)
+                    || schedule
+                        .get(&target.path)
+                        .is_none_or(|v| v.next_scan <= now)
+            });
+        }
+
+        self.scan_targets(targets, self.config.window()).await;
+
         Ok(())
     }
 
-    async fn get_commits_in_repo(&self, path: &Path) -> Result<(), String> {
-        let repo_name = match path.file_name() {
-            Some(v) => match v.to_str() {
-                Some(v) => v.to_string(),
-                None => {
-                    return Err("Unable to read os-string to internal string.".to_string());
-                }
-            },
+    /// Discovers repositories below the configured roots and merges them with
+    /// the explicitly configured ones.
+    async fn resolve_targets(&self) -> Result<Vec<RepositoryTarget>, String> {
+        let (repositories, warnings) = discover_repositories(
+            &self.config.roots(),
+            self.config.discovery.max_depth,
+            &self.ignore,
+        )
+        .await;
+        for warning in warnings {
+            self.plugin_data.report_error_string(warning);
+        }
+
+        let explicit: Vec<(PathBuf, &RepositoryConfig)> = self
+            .config
+            .repositories
+            .iter()
+            .map(|v| (canonical_path(&v.path), v))
+            .collect();
+
+        let mut targets: Vec<RepositoryTarget> = Vec::new();
+        for (path, repository) in &explicit {
+            if !repository.enabled {
+                continue;
+            }
+
+            let name = repository_name(path, repository)?;
+
+            let authors = self
+                .repository_authors
+                .get(path)
+                .cloned()
+                .unwrap_or_else(|| self.authors.clone());
+
+            targets.push(RepositoryTarget {
+                path: path.clone(),
+                refs: repository.refs.clone().unwrap_or_else(|| self.config.refs.clone()),
+                authors,
+                references: self
+                    .repository_references
+                    .get(&name)
+                    .cloned()
+                    .unwrap_or_else(|| self.references.clone()),
+                name,
+            });
+        }
+
+        targets.extend(
+            repositories
+                .into_iter()
+                .filter(|repository| {
+                    let path = canonical_path(&repository.path);
+                    !explicit.iter().any(|(v, _)| *v == path)
+                })
+                .map(|repository| RepositoryTarget {
+                    path: repository.path,
+                    name: repository.name,
+                    refs: self.config.refs.clone(),
+                    authors: self.authors.clone(),
+                    references: self.references.clone(),
+                }),
+        );
+
+        // Stored events only know the name of their repository, two repositories
+        // sharing one would mix up their commits. Configured repositories come
+        // first, so they keep their name over discovered ones.
+        let mut names: HashMap<String, PathBuf> = HashMap::new();
+        targets.retain(|target| match names.get(&target.name) {
+            Some(other) => {
+                self.plugin_data.report_error_string(format!(
+                    "Skipping repository, its name is already used by another one, give one of them a `name` in `repositories` to track both: \nPath: {} \nName: {} \nUsed by: {}",
+                    target.path.display(),
+                    target.name,
+                    other.display()
+                ));
+                false
+            }
             None => {
-                return Err(
-                    "Unable to identify repository name. There is no last path component.".to_string());
+                names.insert(target.name.clone(), target.path.clone());
+                true
+            }
+        });
+
+        Ok(targets)
+    }
+
+    /// Returns whether every repository was scanned successfully.
+    async fn scan_targets(&self, targets: Vec<RepositoryTarget>, window: ScanWindow) -> bool {
+        // Repositories are scanned side by side and each one is written to the
+        // database as soon as it is done. One broken repository must not keep the
+        // others from being scanned.
+        let total = targets.len();
+        let failures: Vec<(String, PathBuf, String)> = futures::stream::iter(targets)
+            .map(|target| async move {
+                let (name, path) = (target.name.clone(), target.path.clone());
+                let result = self.get_commits_in_repo(target, window).await;
+                self.reschedule(&path, &result).await;
+                result.err().map(|e| (name, path, e))
+            })
+            .buffer_unordered(self.config.parallelism.max(1))
+            .filter_map(|v| async move { v })
+            .collect()
+            .await;
+
+        if !failures.is_empty() {
+            self.plugin_data.report_error_string(format!(
+                "Unable to scan {} of {} repositories ({:.0}% failed):\n{}",
+                failures.len(),
+                total,
+                failures.len() as f64 / total as f64 * 100.0,
+                failures
+                    .iter()
+                    .map(|(name, path, e)| format!("- {} ({}): {}", name, path.display(), e))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+
+        failures.is_empty()
+    }
+
+    async fn reschedule(&self, path: &Path, result: &Result<Option<DateTime<Utc>>, String>) {
+        let mut schedule = self.schedule.lock().await;
+        let entry = schedule.entry(path.to_owned()).or_default();
+        match result {
+            Ok(last_activity) => {
+                entry.failures = 0;
+                if last_activity.is_some() {
+                    entry.last_activity = *last_activity;
+                }
             }
+            Err(_) => entry.failures += 1,
+        }
+        entry.next_scan =
+            Utc::now() + self.config.polling.next_interval(entry.last_activity, entry.failures);
+    }
+
+    /// Imports new commits of a repository and returns the time of its newest tip.
+    async fn get_commits_in_repo(
+        &self,
+        target: RepositoryTarget,
+        window: ScanWindow,
+    ) -> Result<Option<DateTime<Utc>>, String> {
+        let RepositoryTarget {
+            path,
+            name: repo_name,
+            refs: selection,
+            authors,
+            references,
+        } = target;
+        let use_mailmap = self.config.mailmap;
+        let diff_stats = self.config.diff_stats.clone();
+        let track_tags = self.config.track_tags;
+        let track_reflog = self.config.reflog;
+        let rewrite_policy = self.config.rewrites;
+        let state_key = path.display().to_string();
+        let (initial, watermarks, reflog_marks, local_branches, tag_targets) = {
+            let state = self.state.lock().await;
+            (
+                !state.watermarks.contains_key(&state_key),
+                state.watermarks.get(&state_key).cloned().unwrap_or_default(),
+                state.reflog_marks.get(&state_key).cloned().unwrap_or_default(),
+                state.local_branches.get(&state_key).cloned().unwrap_or_default(),
+                state.tags.get(&state_key).cloned().unwrap_or_default(),
+            )
         };
 
-        let path = path.to_owned();
+        let (repository_path, repository_name) = (path.clone(), repo_name.clone());
+        let walk_authors = authors.clone();
 
-        let handle = thread::spawn(move || {
+        let handle = task::spawn_blocking(move || {
             let repo = match Repository::open(&path) {
                 Ok(v) => v,
                 Err(e) => {
@@ -151,155 +981,1061 @@ impl Plugin {
                 }
             };
 
-            let mut walk = match repo.revwalk() {
+            // Freshly initialised repositories have nothing to import yet. They are
+            // picked up by a later scan once they got their first commit.
+            if repo.is_empty().unwrap_or(false) {
+                return Ok(ScanResult::default());
+            }
+
+            let tips = match get_ref_tips(&repo, &selection) {
                 Ok(v) => v,
                 Err(e) => {
                     return Err(format!(
-                        "Unable to read commits of repository: \nPath: {} \nError: {}",
+                        "Unable to resolve references of repository: \nPath: {} \nError: {}",
                         path.display(),
                         e
-                    ))
+                    ));
+                }
+            };
+
+            // Watermarks used to be keyed by short names, which a branch and a
+            // tag of the same name shared.
+            let legacy_watermarks = watermarks.keys().any(|v| v != "HEAD" && !v.starts_with("refs/"));
+            let watermarks: HashMap<String, String> = watermarks
+                .into_iter()
+                .map(|(ref_name, tip)| match ref_name == "HEAD" || ref_name.starts_with("refs/") {
+                    true => (ref_name, tip),
+                    false => match repo
+                        .resolve_reference_from_short_name(&ref_name)
+                        .ok()
+                        .and_then(|v| v.name().map(String::from))
+                    {
+                        Some(v) => (v, tip),
+                        None => (ref_name, tip),
+                    },
+                })
+                .collect();
+
+            let last_activity = tips
+                .values()
+                .filter_map(|v| repo.find_commit(*v).ok())
+                .map(|v| git_time_to_utc(v.time()))
+                .max();
+
+            let mailmap = match use_mailmap {
+                true => repo.mailmap().ok(),
+                false => None,
+            };
+
+            // A reference that is gone or no longer contains its previous tip may
+            // have taken stored commits with it. Without watermarks nothing is
+            // known about the stored commits, e.g. after the author filter changed.
+            let rewritten = watermarks.is_empty() || watermarks.iter().any(|(ref_name, previous_tip)| {
+                match (tips.get(ref_name), git2::Oid::from_str(previous_tip)) {
+                    (Some(tip), Ok(previous_tip)) => {
+                        *tip != previous_tip
+                            && !repo.graph_descendant_of(*tip, previous_tip).unwrap_or(false)
+                    }
+                    _ => true,
                 }
+            });
+            let reachable = match rewritten && rewrite_policy != RewritePolicy::Keep {
+                true => match reachable_commits(&repo, &tips.values().copied().collect::<Vec<_>>()) {
+                    Ok(v) => Some(v),
+                    Err(e) => {
+                        return Err(format!(
+                            "Unable to collect reachable commits of repository: \nPath: {} \nError: {}",
+                            path.display(),
+                            e
+                        ));
+                    }
+                },
+                false => None,
             };
 
-            match (walk.set_sorting(git2::Sort::TIME), walk.push_head()) {
-                (Ok(_), Ok(_)) => {}
-                (Err(e), Err(e_2)) => {
-                    return Err(format!("Was unable to set sorting and append head to revwalk. \nPath: {} \nError Sorting: {} \nError Head: {}", path.display(), e, e_2));
+            let mut refs_by_commit: HashMap<git2::Oid, Vec<String>> = HashMap::new();
+            let mut commit_times: HashMap<git2::Oid, DateTime<Utc>> = HashMap::new();
+            // Only the initial import is capped, later scans must not lose the
+            // older part of a large pull.
+            let max_commits = window.max_commits.filter(|_| initial);
+
+            // Everything reachable from a previously imported tip is stored already,
+            // whichever reference it was imported through. Tips that were rewritten
+            // away but still exist hide nothing but what they shared with the new history.
+            let known_tips: Vec<git2::Oid> = match window.full {
+                true => Vec::new(),
+                false => watermarks
+                    .values()
+                    .filter_map(|v| git2::Oid::from_str(v).ok())
+                    .filter(|v| repo.find_commit(*v).is_ok())
+                    .collect(),
+            };
+
+            for (ref_name, tip) in tips.clone() {
+                // Only walk what was added since the last scan. New references, such
+                // as a branch forked from main, only walk the commits unique to them.
+                let unchanged = watermarks
+                    .get(&ref_name)
+                    .is_some_and(|v| git2::Oid::from_str(v).ok() == Some(tip));
+                if unchanged && !window.full {
+                    continue;
                 }
-                (Err(e), _) => {
+
+                let mut walk = match repo.revwalk() {
+                    Ok(v) => v,
+                    Err(e) => {
+                        return Err(format!(
+                            "Unable to read commits of repository: \nPath: {} \nError: {}",
+                            path.display(),
+                            e
+                        ))
+                    }
+                };
+
+                if let Err(e) = walk.push(tip) {
                     return Err(format!(
-                        "Was unable to set sorting: \nPath: {} \nError: {}",
+                        "Was unable to append reference to revwalk: \nPath: {} \nReference: {} \nError: {}",
                         path.display(),
+                        ref_name,
                         e
                     ));
                 }
-                (_, Err(e)) => {
+
+                // Newest first, so the walk can stop at the start of the window.
+                if let Err(e) = walk.set_sorting(git2::Sort::TIME) {
                     return Err(format!(
-                        "Was unable to append head: \nPath: {} \nError: {}",
+                        "Unable to sort revwalk: \nPath: {} \nError: {}",
                         path.display(),
                         e
                     ));
                 }
-            }
-
-            let mut commits: Vec<Commit> = Vec::new();
 
-            for step in walk {
-                let step_id = match step {
-                    Ok(v) => v,
-                    Err(e) => {
+                for known_tip in &known_tips {
+                    if let Err(e) = walk.hide(*known_tip) {
                         return Err(format!(
-                            "Unable to walk along commit graph: \nPath: {} \nError: {}",
+                            "Was unable to hide already imported commits from revwalk: \nPath: {} \nReference: {} \nError: {}",
                             path.display(),
+                            ref_name,
                             e
                         ));
                     }
-                };
+                }
 
-                let commit = match repo.find_commit(step_id) {
-                    Ok(v) => v,
-                    Err(e) => {
-                        return Err(format!("Unable to find a commit from revwalk in repository: \nPath: {} \nError: {}", path.display(), e));
-                    }
-                };
+                let mut walked = 0;
+                for step in walk {
+                    let step_id = match step {
+                        Ok(v) => v,
+                        Err(e) => {
+                            return Err(format!(
+                                "Unable to walk along commit graph: \nPath: {} \nError: {}",
+                                path.display(),
+                                e
+                            ));
+                        }
+                    };
 
-                let msg = match commit.message() {
-                    Some(v) => v,
-                    None => {
-                        return Err(format!(
-                            "Unable to read commit message: \nPath: {}",
-                            path.display()
-                        ));
+                    let time = match repo.find_commit(step_id) {
+                        Ok(v) => git_time_to_utc(v.time()),
+                        Err(e) => {
+                            return Err(format!("Unable to find a commit from revwalk in repository: \nPath: {} \nError: {}", path.display(), e));
+                        }
+                    };
+                    if window.since.is_some_and(|v| time < v)
+                        || max_commits.is_some_and(|v| walked >= v)
+                    {
+                        break;
                     }
-                };
-
-                let time = commit.time();
-
-                let offset = FixedOffset::west_opt(time.offset_minutes() * 60).unwrap();
-                let timestamp = offset.timestamp_millis_opt(time.seconds() * 1000).unwrap();
-                let utc = DateTime::<Utc>::from(timestamp);
+                    if window.until.is_some_and(|v| time > v) {
+                        continue;
+                    }
+                    walked += 1;
 
-                let parsed_commit = Commit {
-                    id: step_id.to_string(),
-                    message: msg.to_string(),
-                    time: utc,
-                    repository_name: repo_name.clone(),
-                    author: commit.author().to_string(),
-                };
+                    commit_times.insert(step_id, time);
+                    refs_by_commit
+                        .entry(step_id)
+                        .or_default()
+                        .push(short_ref_name(&ref_name).to_string());
+                }
+            }
 
-                commits.push(parsed_commit);
+            // Every reference brought its newest commits, only the newest of all are kept.
+            if let Some(max_commits) = max_commits {
+                if refs_by_commit.len() > max_commits {
+                    let mut newest: Vec<(git2::Oid, DateTime<Utc>)> =
+                        commit_times.into_iter().collect();
+                    newest.sort_by_key(|v| std::cmp::Reverse(v.1));
+                    let keep: HashSet<git2::Oid> =
+                        newest.into_iter().take(max_commits).map(|(v, _)| v).collect();
+                    refs_by_commit.retain(|v, _| keep.contains(v));
+                }
             }
 
-            Ok(commits)
+            // Commits imported through one reference are reached by others later,
+            // e.g. once a branch is merged into main or forked from it. Every
+            // reference that moved is walked back to its own previous tip to find them.
+            let mut contained: HashMap<String, Vec<String>> = HashMap::new();
+            if !initial && !window.full {
+                for (ref_name, tip) in &tips {
+                    let previous = watermarks
+                        .get(ref_name)
+                        .and_then(|v| git2::Oid::from_str(v).ok())
+                        .filter(|v| repo.find_commit(*v).is_ok());
+                    if previous == Some(*tip) {
+                        continue;
+                    }
+
+                    match newly_contained(&repo, *tip, previous, window.since) {
+                        Ok(v) => {
+                            let ids: Vec<String> = v
+                                .into_iter()
+                                .filter(|v| !refs_by_commit.contains_key(v))
+                                .map(|v| commit_event_id(&repo_name, v))
+                                .collect();
+                            if !ids.is_empty() {
+                                contained.insert(short_ref_name(ref_name).to_string(), ids);
+                            }
+                        }
+                        Err(e) => {
+                            return Err(format!(
+                                "Unable to find the commits a reference reaches: \nPath: {} \nReference: {} \nError: {}",
+                                path.display(),
+                                ref_name,
+                                e
+                            ));
+                        }
+                    }
+                }
+            }
+
+            let mut commits: Vec<Commit> = Vec::new();
+            let mut warnings: Vec<String> = Vec::new();
+
+            for (step_id, refs) in refs_by_commit {
+                let commit = match repo.find_commit(step_id) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        return Err(format!("Unable to find a commit from revwalk in repository: \nPath: {} \nError: {}", path.display(), e));
+                    }
+                };
+
+                let (author, committer) = match &mailmap {
+                    Some(mailmap) => (
+                        mailmap.resolve_signature(&commit.author()).unwrap_or_else(|_| commit.author().to_owned()),
+                        mailmap.resolve_signature(&commit.committer()).unwrap_or_else(|_| commit.committer().to_owned()),
+                    ),
+                    None => (commit.author().to_owned(), commit.committer().to_owned()),
+                };
+
+                let (msg, warning) =
+                    decode_message(commit.message_bytes(), commit.message_encoding());
+                if let Some(warning) = warning {
+                    warnings.push(format!(
+                        "Unable to cleanly decode commit message: \nPath: {} \nCommit: {} \nWarning: {}",
+                        path.display(),
+                        step_id,
+                        warning
+                    ));
+                }
+
+                let mut parsed_commit = Commit {
+                    // The same commit in a fork and its upstream are two events.
+                    id: commit_event_id(&repo_name, step_id),
+                    message: msg,
+                    repository_name: repo_name.clone(),
+                    author_time: git_time_to_utc(author.when()),
+                    author_offset_minutes: author.when().offset_minutes(),
+                    committer_time: git_time_to_utc(committer.when()),
+                    committer_offset_minutes: committer.when().offset_minutes(),
+                    author_name: String::from_utf8_lossy(author.name_bytes()).to_string(),
+                    author_email: String::from_utf8_lossy(author.email_bytes()).to_string(),
+                    committer_name: String::from_utf8_lossy(committer.name_bytes()).to_string(),
+                    committer_email: String::from_utf8_lossy(committer.email_bytes()).to_string(),
+                    refs,
+                    diff: None,
+                };
+
+                // Commits of others are dropped before their diff is computed,
+                // which is most of the work in large shared repositories.
+                if !walk_authors.is_empty() && !parsed_commit.has_participant(&walk_authors) {
+                    continue;
+                }
+                if diff_stats.enabled {
+                    // A diff that cannot be computed is not worth losing the commit over.
+                    parsed_commit.diff = get_diff_summary(&repo, &commit, &diff_stats).ok();
+                }
+
+                commits.push(parsed_commit);
+            }
+
+            let all_tags = match track_tags {
+                true => match read_tags(&repo, &repo_name) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        return Err(format!(
+                            "Unable to read tags of repository: \nPath: {} \nError: {}",
+                            path.display(),
+                            e
+                        ));
+                    }
+                },
+                false => Vec::new(),
+            };
+
+            let current_tag_targets: HashMap<String, String> = all_tags
+                .iter()
+                .map(|v| (v.tag.tag_name.clone(), v.tag.target.clone()))
+                .collect();
+            let tags: Vec<Tag> = all_tags
+                .into_iter()
+                .filter(|v| window.full || tag_targets.get(&v.tag.tag_name) != Some(&v.tag.target))
+                .filter(|v| window.contains(v.time))
+                .filter(|v| walk_authors.is_empty() || is_tagged_by(&repo, mailmap.as_ref(), &v.tag, &walk_authors))
+                .collect();
+
+            // A full scan, such as a backfill, reads older entries again.
+            let read_since = match window.full {
+                true => HashMap::new(),
+                false => reflog_marks.clone(),
+            };
+            let (reflog, current_branches) = match track_reflog {
+                true => match (
+                    read_reflogs(&repo, &repo_name, &read_since),
+                    local_branch_tips(&repo),
+                ) {
+                    (Ok(v), Ok(current_branches)) => (v, current_branches),
+                    (Err(e), _) | (_, Err(e)) => {
+                        return Err(format!(
+                            "Unable to read reflog of repository: \nPath: {} \nError: {}",
+                            path.display(),
+                            e
+                        ));
+                    }
+                },
+                false => (Vec::new(), HashMap::new()),
+            };
+            // Entries outside the window are not marked as read, a later scan
+            // with another window can still import them.
+            let mut reflog: Vec<ReflogEntry> =
+                reflog.into_iter().filter(|v| window.contains(v.time)).collect();
+
+            let mut new_reflog_marks = reflog_marks;
+            for entry in &reflog {
+                let mark = new_reflog_marks
+                    .entry(entry.entry.reference.clone())
+                    .or_insert(i64::MIN);
+                *mark = (*mark).max(entry.time.timestamp());
+            }
+
+            // A deleted branch takes its reflog with it, so its deletion is only
+            // noticed by comparing the branches to the previous scan. A backfill
+            // leaves that to the next regular scan.
+            let deleted_at = Utc::now();
+            reflog.extend(
+                local_branches
+                    .iter()
+                    .filter(|(branch, _)| !current_branches.contains_key(*branch))
+                    .filter(|_| !window.full && window.contains(deleted_at))
+                    .map(|(branch, tip)| ReflogEntry {
+                        id: format!("{}:{}:deleted:{}", repo_name, branch, tip),
+                        time: deleted_at,
+                        entry: DatabaseReflogEntry {
+                            repository_name: repo_name.clone(),
+                            reference: branch.clone(),
+                            action: ReflogAction::Deleted,
+                            old: tip.clone(),
+                            new: git2::Oid::zero().to_string(),
+                            message: format!("branch: Deleted {}", branch),
+                            index: 0,
+                            committer_name: String::new(),
+                            committer_email: String::new(),
+                        },
+                    }),
+            );
+
+            let new_watermarks: HashMap<String, String> = tips
+                .iter()
+                .map(|(ref_name, tip)| (ref_name.clone(), tip.to_string()))
+                .collect();
+
+            Ok(ScanResult {
+                commits,
+                warnings,
+                tags,
+                tag_targets: current_tag_targets,
+                reflog,
+                watermarks: match !initial && !legacy_watermarks && watermarks == new_watermarks {
+                    true => None,
+                    false => Some(new_watermarks),
+                },
+                reflog_marks: new_reflog_marks,
+                local_branches: current_branches,
+                last_activity,
+                reachable,
+                contained,
+            })
         });
 
-        let commits = match handle.join() {
+        let result = match handle.await {
             Ok(v) => match v {
                 Ok(v) => v,
                 Err(e) => return Err(e)
             },
             Err(e) => {
-                return Err(format!("Unable to join handle on update thread: {:?}", e))
+                return Err(format!("Unable to join scanning task: {}", e))
             }
         };
 
-        self.insert_new_commits_into_database(&commits).await
+        for warning in result.warnings {
+            self.plugin_data.report_error_string(warning);
+        }
+
+        if let Some(reachable) = result.reachable {
+            self.reconcile_commits(&repository_path, &repository_name, reachable, &result.commits, &authors)
+                .await?;
+        }
+        self.insert_new_commits_into_database(&result.commits, &authors, &references)
+            .await?;
+        self.add_commit_refs(result.contained).await?;
+        self.upsert_events(
+            "tag",
+            result
+                .tags
+                .into_iter()
+                .map(|v| GitEvent {
+                    timing: Timing::Instant(v.time),
+                    id: v.id,
+                    plugin: AvailablePlugins::timeline_plugin_git,
+                    event: DatabaseGitEvent::Tag(v.tag),
+                })
+                .collect(),
+        )
+        .await?;
+        self.upsert_events(
+            "reflog",
+            result
+                .reflog
+                .into_iter()
+                .map(|v| GitEvent {
+                    timing: Timing::Instant(v.time),
+                    id: v.id,
+                    plugin: AvailablePlugins::timeline_plugin_git,
+                    event: DatabaseGitEvent::Reflog(v.entry),
+                })
+                .collect(),
+        )
+        .await?;
+
+        // A backfill only fills in the past. Watermarks, tags and branches stay
+        // where the last regular scan left them, so it picks up everything newer.
+        if window.full {
+            return Ok(result.last_activity);
+        }
+
+        let mut state = self.state.lock().await;
+        let mut changed = false;
+        if let Some(watermarks) = result.watermarks {
+            state.watermarks.insert(state_key.clone(), watermarks);
+            changed = true;
+        }
+        if track_tags {
+            changed |= state.tags.get(&state_key) != Some(&result.tag_targets);
+            state.tags.insert(state_key.clone(), result.tag_targets);
+        }
+        if track_reflog {
+            changed |= state.reflog_marks.get(&state_key) != Some(&result.reflog_marks)
+                || state.local_branches.get(&state_key) != Some(&result.local_branches);
+            state.reflog_marks.insert(state_key.clone(), result.reflog_marks);
+            state.local_branches.insert(state_key, result.local_branches);
+        }
+        if changed {
+            self.save_state(&state).await?;
+        }
+
+        Ok(result.last_activity)
     }
 
-    async fn insert_new_commits_into_database(&self, commits: &Vec<Commit>) -> Result<(), String> {
-        let ids: Vec<&str> = commits.iter().map(|v| v.id.as_str()).collect();
+    /// Applies the rewrite policy to stored commits of a repository that are not
+    /// `reachable` anymore. Commits that became reachable again lose their mark.
+    /// With [`RewritePolicy::Link`], vanished commits are linked to the `scanned`
+    /// commits passing `authors`.
+    async fn reconcile_commits(
+        &self,
+        path: &Path,
+        repo_name: &str,
+        reachable: HashSet<git2::Oid>,
+        scanned: &[Commit],
+        authors: &AuthorFilter,
+    ) -> Result<(), String> {
+        let collection = self
+            .plugin_data
+            .database
+            .get_events::<DatabaseGitEvent>()
+            .clone_with_type::<Document>();
+        let repository_filter = Database::combine_documents(
+            kind_filter("commit"),
+            doc! {
+                "event.repository_name": repo_name
+            },
+        );
 
-        let already_inserted_commits: Vec<String> = match self
-        .plugin_data
-        .database
-        .get_events::<DatabaseCommit>()
-        .find(
-            Database::combine_documents(
-                Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_git),
+        let stored: Vec<Document> = match collection
+            .find(
+                repository_filter.clone(),
+                FindOptions::builder()
+                    .projection(doc! {
+                        "id": 1,
+                        "event.rewritten": 1
+                    })
+                    .build(),
+            )
+            .await
+        {
+            Ok(v) => match v.try_collect().await {
+                Ok(v) => v,
+                Err(e) => return Err(format!("Unable to collect stored commits: {}", e)),
+            },
+            Err(e) => return Err(format!("Error loading stored commits from database: {}", e)),
+        };
+
+        let mut known = HashSet::new();
+        let mut vanished = Vec::new();
+        let mut restored = Vec::new();
+        for document in stored {
+            let (Ok(id), rewritten) = (
+                document.get_str("id"),
+                document
+                    .get_document("event")
+                    .and_then(|v| v.get_bool("rewritten"))
+                    .unwrap_or(false),
+            ) else {
+                continue;
+            };
+            let Some(oid) = commit_oid(repo_name, id) else {
+                continue;
+            };
+
+            known.insert(oid);
+            match (reachable.contains(&oid), rewritten) {
+                (false, false) => vanished.push(oid),
+                (true, true) => restored.push(id.to_string()),
+                _ => (),
+            }
+        }
+
+        if !restored.is_empty() {
+            if let Err(e) = collection
+                .update_many(
+                    Database::combine_documents(
+                        repository_filter.clone(),
+                        doc! {
+                            "id": {
+                                "$in": restored
+                            }
+                        },
+                    ),
                     doc! {
-                        "id": {
-                            "$in": ids
+                        "$set": {
+                            "event.rewritten": false
+                        },
+                        "$unset": {
+                            "event.replaced_by": ""
                         }
                     },
-                ),
+                    None,
+                )
+                .await
+            {
+                return Err(format!("Unable to unmark restored commits: {}", e));
+            }
+        }
+
+        if vanished.is_empty() {
+            return Ok(());
+        }
+
+        let vanished_ids: Vec<String> = vanished
+            .iter()
+            .map(|v| commit_event_id(repo_name, *v))
+            .collect();
+        let vanished_filter = Database::combine_documents(
+            repository_filter,
+            doc! {
+                "id": {
+                    "$in": &vanished_ids
+                }
+            },
+        );
+
+        let replacements: HashMap<String, String> = match self.config.rewrites {
+            RewritePolicy::Keep => return Ok(()),
+            RewritePolicy::Delete => {
+                if let Err(e) = collection.delete_many(vanished_filter, None).await {
+                    return Err(format!("Unable to delete rewritten commits: {}", e));
+                }
+                return Ok(());
+            }
+            RewritePolicy::Mark => HashMap::new(),
+            RewritePolicy::Link => {
+                // Replacements are among the commits that are new to the database,
+                // which only holds commits passing the author filter.
+                let candidates: Vec<git2::Oid> = scanned
+                    .iter()
+                    .filter(|v| authors.is_empty() || v.has_participant(authors))
+                    .filter_map(|v| commit_oid(repo_name, &v.id))
+                    .filter(|v| !known.contains(v))
+                    .collect();
+                let path = path.to_owned();
+                let repo_name = repo_name.to_string();
+
+                let handle = task::spawn_blocking(move || {
+                    let repo = match Repository::open(&path) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            return Err(format!(
+                                "Unable to read repository: \nPath: {} \nError: {}",
+                                path.display(),
+                                e
+                            ));
+                        }
+                    };
+
+                    let by_patch_id: HashMap<git2::Oid, git2::Oid> = candidates
+                        .into_iter()
+                        .filter_map(|v| patch_id(&repo, v).ok().map(|p| (p, v)))
+                        .collect();
+
+                    // Vanished commits whose objects were already pruned can not be linked.
+                    Ok(vanished
+                        .into_iter()
+                        .filter_map(|v| {
+                            let replacement = by_patch_id.get(&patch_id(&repo, v).ok()?)?;
+                            Some((
+                                commit_event_id(&repo_name, v),
+                                commit_event_id(&repo_name, *replacement),
+                            ))
+                        })
+                        .collect())
+                });
+
+                match handle.await {
+                    Ok(v) => v?,
+                    Err(e) => return Err(format!("Unable to join patch-id task: {}", e)),
+                }
+            }
+        };
+
+        if let Err(e) = collection
+            .update_many(
+                vanished_filter,
+                doc! {
+                    "$set": {
+                        "event.rewritten": true
+                    }
+                },
                 None,
             )
             .await
+        {
+            return Err(format!("Unable to mark rewritten commits: {}", e));
+        }
+
+        for (id, replaced_by) in replacements {
+            if let Err(e) = collection
+                .update_one(
+                    Database::combine_documents(
+                        kind_filter("commit"),
+                        doc! {
+                            "id": id
+                        },
+                    ),
+                    doc! {
+                        "$set": {
+                            "event.replaced_by": replaced_by
+                        }
+                    },
+                    None,
+                )
+                .await
+            {
+                return Err(format!("Unable to link rewritten commit to its replacement: {}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn save_state(&self, state: &ScanState) -> Result<(), String> {
+        let json = match serde_json::to_string(state) {
+            Ok(v) => v,
+            Err(e) => return Err(format!("Unable to serialize scan state: {}", e)),
+        };
+
+        if let Err(e) = self
+            .plugin_data
+            .database
+            .get_events::<DatabaseGitEvent>()
+            .replace_one(
+                Database::combine_documents(
+                    kind_filter("state"),
+                    doc! {
+                        "id": STATE_ID
+                    },
+                ),
+                GitEvent {
+                    timing: Timing::Instant(Utc::now()),
+                    id: STATE_ID.to_string(),
+                    plugin: AvailablePlugins::timeline_plugin_git,
+                    event: DatabaseGitEvent::State(StoredState { json }),
+                },
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+        {
+            return Err(format!("Unable to store scan state: {}", e));
+        }
+
+        Ok(())
+    }
+
+    async fn insert_new_commits_into_database(
+        &self,
+        commits: &[Commit],
+        authors: &AuthorFilter,
+        references: &ReferenceExtractor,
+    ) -> Result<(), String> {
+        let events = commits
+            .iter()
+            .map(|commit| {
+                (
+                    commit,
+                    DatabaseCommit {
+                        author: None,
+                        author_name: commit.author_name.clone(),
+                        author_email: commit.author_email.clone(),
+                        author_time: Some(commit.author_time),
+                        author_offset_minutes: Some(commit.author_offset_minutes),
+                        committer_name: commit.committer_name.clone(),
+                        committer_email: commit.committer_email.clone(),
+                        committer_time: Some(commit.committer_time),
+                        committer_offset_minutes: Some(commit.committer_offset_minutes),
+                        message: commit.message.clone(),
+                        repository_name: commit.repository_name.clone(),
+                        refs: commit.refs.clone(),
+                        diff: commit.diff.clone(),
+                        rewritten: false,
+                        replaced_by: None,
+                        parsed: parse_message(&commit.message),
+                        references: references.extract(&commit.message),
+                    },
+                )
+            })
+            .filter(|(_, v)| authors.is_empty() || v.has_participant(authors))
+            .map(|(commit, event)| GitEvent {
+                timing: commit_timing(
+                    commit.author_time,
+                    commit.committer_time,
+                    self.config.timestamp_source,
+                ),
+                id: commit.id.clone(),
+                plugin: AvailablePlugins::timeline_plugin_git,
+                event: DatabaseGitEvent::Commit(Box::new(event)),
+            })
+            .collect();
+
+        self.upsert_events("commit", events).await
+    }
+
+    /// Adds references to the stored commits they reach now.
+    async fn add_commit_refs(&self, contained: HashMap<String, Vec<String>>) -> Result<(), String> {
+        let collection = self.plugin_data.database.get_events::<DatabaseGitEvent>();
+        for (ref_name, ids) in contained {
+            for chunk in ids.chunks(UPSERT_CHUNK_SIZE) {
+                if let Err(e) = collection
+                    .update_many(
+                        Database::combine_documents(
+                            kind_filter("commit"),
+                            doc! {
+                                "id": {
+                                    "$in": chunk
+                                }
+                            },
+                        ),
+                        doc! {
+                            "$addToSet": {
+                                "event.refs": &ref_name
+                            }
+                        },
+                        None,
+                    )
+                    .await
+                {
+                    return Err(format!("Unable to add reference {} to stored commits: {}", ref_name, e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Inserts events of one kind that are not stored yet. Stored events are left
+    /// as they are, except that new references are added to their `refs`.
+    ///
+    /// Stored ids are looked up in chunks and the missing events are inserted in
+    /// one unordered batch per chunk. An event that a concurrent scan stored in
+    /// between is rejected by the unique id index and only gets its references added.
+    async fn upsert_events(&self, kind: &str, events: Vec<GitEvent>) -> Result<(), String> {
+        let collection = self.plugin_data.database.get_events::<DatabaseGitEvent>();
+        let documents = collection.clone_with_type::<Document>();
+        let mut errors: Vec<String> = Vec::new();
+
+        for chunk in events.chunks(UPSERT_CHUNK_SIZE) {
+            let ids: Vec<&str> = chunk.iter().map(|v| v.id.as_str()).collect();
+            let mut cursor = match documents
+                .find(
+                    Database::combine_documents(
+                        kind_filter(kind),
+                        doc! {
+                            "id": {
+                                "$in": ids
+                            }
+                        },
+                    ),
+                    FindOptions::builder()
+                        .projection(doc! { "id": 1, "event.refs": 1 })
+                        .build(),
+                )
+                .await
+            {
+                Ok(v) => v,
+                Err(e) => return Err(format!("Unable to look up stored {} events: {}", kind, e)),
+            };
+
+            // Id -> references the stored event is known under.
+            let mut stored: HashMap<String, HashSet<String>> = HashMap::new();
+            while let Some(v) = cursor.next().await {
+                let document = match v {
+                    Ok(v) => v,
+                    Err(e) => return Err(format!("Unable to read stored {} event: {}", kind, e)),
+                };
+                let Ok(id) = document.get_str("id") else {
+                    continue;
+                };
+                let refs = document
+                    .get_document("event")
+                    .and_then(|v| v.get_array("refs"))
+                    .map(|v| v.iter().filter_map(|v| v.as_str()).map(String::from).collect())
+                    .unwrap_or_default();
+                stored.insert(id.to_string(), refs);
+            }
+
+            let (existing, missing): (Vec<&GitEvent>, Vec<&GitEvent>) =
+                chunk.iter().partition(|v| stored.contains_key(&v.id));
+            // Id -> references to add.
+            let mut updates: Vec<(String, Vec<String>)> = existing
+                .into_iter()
+                .map(|v| {
+                    let known = &stored[&v.id];
+                    (
+                        v.id.clone(),
+                        event_refs(v).iter().filter(|v| !known.contains(*v)).cloned().collect(),
+                    )
+                })
+                .collect();
+
+            if !missing.is_empty() {
+                let options = InsertManyOptions::builder().ordered(false).build();
+                if let Err(e) = collection.insert_many(missing.iter().copied(), options).await {
+                    match e.kind.as_ref() {
+                        ErrorKind::BulkWrite(failure) if failure.write_concern_error.is_none() => {
+                            for error in failure.write_errors.iter().flatten() {
+                                match (error.code, missing.get(error.index)) {
+                                    (DUPLICATE_KEY, Some(event)) => {
+                                        updates.push((event.id.clone(), event_refs(event).to_vec()))
+                                    }
+                                    _ => errors.push(error.message.clone()),
+                                }
+                            }
+                        }
+                        _ => errors.push(e.to_string()),
+                    }
+                }
+            }
+
+            updates.retain(|(_, refs)| !refs.is_empty());
+            let update_errors: Vec<String> = futures::stream::iter(updates)
+                .map(|(id, refs)| {
+                    let collection = &collection;
+                    async move {
+                        collection
+                            .update_one(
+                                Database::combine_documents(
+                                    kind_filter(kind),
+                                    doc! {
+                                        "id": &id
+                                    },
+                                ),
+                                doc! {
+                                    "$addToSet": {
+                                        "event.refs": {
+                                            "$each": refs
+                                        }
+                                    }
+                                },
+                                None,
+                            )
+                            .await
+                            .err()
+                            .map(|e| format!("{}: {}", id, e))
+                    }
+                })
+                .buffer_unordered(16)
+                .filter_map(|v| async move { v })
+                .collect()
+                .await;
+            errors.extend(update_errors);
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(format!(
+                "Unable to store {} of the {} events:\n{}",
+                errors.len(),
+                kind,
+                errors.join("\n")
+            )),
+        }
+    }
+
+    /// Moves stored commits to the times the configured timestamp source gives them.
+    async fn retime_existing_commits(&self) -> Result<(), String> {
+        let collection = self
+            .plugin_data
+            .database
+            .get_events::<DatabaseCommit>();
+
+        let mut cursor = match collection.find(kind_filter("commit"), None).await {
+            Ok(v) => v,
+            Err(e) => {
+                return Err(format!("Error loading stored commits for re-timing: {}", e));
+            }
+        };
+
+        while let Some(v) = cursor.next().await {
+            let commit = match v {
+                Ok(v) => v,
+                Err(e) => {
+                    return Err(format!("Unable to read stored commit for re-timing: {}", e));
+                }
+            };
+
+            // Commits stored before both times were recorded keep their commit time.
+            let (Some(author_time), Some(committer_time)) =
+                (commit.event.author_time, commit.event.committer_time)
+            else {
+                continue;
+            };
+
+            let timing = match bson::to_bson(&commit_timing(
+                author_time,
+                committer_time,
+                self.config.timestamp_source,
+            )) {
+                Ok(v) => v,
+                Err(e) => return Err(format!("Unable to serialize commit timing: {}", e)),
+            };
+            if bson::to_bson(&commit.timing).is_ok_and(|v| v == timing) {
+                continue;
+            }
+
+            if let Err(e) = collection
+                .update_one(
+                    Database::combine_documents(
+                        kind_filter("commit"),
+                        doc! {
+                            "id": &commit.id
+                        },
+                    ),
+                    doc! {
+                        "$set": {
+                            "timing": timing
+                        }
+                    },
+                    None,
+                )
+                .await
+            {
+                return Err(format!("Unable to update timing of stored commit: {}", e));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes stored commits that the current author filter of their repository rejects.
+    async fn refilter_existing_commits(&self, targets: &[RepositoryTarget]) -> Result<(), String> {
+        for target in targets {
+            if target.authors.is_empty() {
+                continue;
+            }
+
+            let stored = match self
+                .plugin_data
+                .database
+                .get_events::<DatabaseCommit>()
+                .find(
+                    Database::combine_documents(
+                        kind_filter("commit"),
+                        doc! {
+                            "event.repository_name": &target.name
+                        },
+                    ),
+                    None,
+                )
+                .await
             {
                 Ok(v) => match v.try_collect::<Vec<Event<DatabaseCommit>>>().await {
-                    Ok(v) => v.into_iter().map(|v| v.id).collect(),
+                    Ok(v) => v,
                     Err(e) => {
-                        return Err(format!(
-                            "Unable to collect all already existing commits: {}",
-                            e
-                        ));
+                        return Err(format!("Unable to collect stored commits for re-filtering: {}", e));
                     }
                 },
                 Err(e) => {
-                    return Err(format!("Error loading commit ids from database: {}", e));
+                    return Err(format!("Error loading stored commits for re-filtering: {}", e));
                 }
             };
-            
-        let mut insert = Vec::new();
-            
-        for commit in commits {
-            if !already_inserted_commits.contains(&commit.id) {
-                insert.push(CommitEvent {
-                    timing: Timing::Instant(commit.time),
-                    id: commit.id.clone(),
-                    plugin: AvailablePlugins::timeline_plugin_git,
-                    event: DatabaseCommit {
-                        author: commit.author.clone(),
-                        message: commit.message.clone(),
-                        repository_name: commit.repository_name.clone(),
-                    },
+
+            let rejected: Vec<String> = stored
+                .into_iter()
+                .map(|mut v| {
+                    v.event.upgrade_legacy_identity();
+                    v.event.upgrade_legacy_message();
+                    v
                 })
+                .filter(|v| !v.event.has_participant(&target.authors))
+                .map(|v| v.id)
+                .collect();
+
+            if rejected.is_empty() {
+                continue;
             }
-        }
-        if !insert.is_empty() {
-            if let Err(e) = self.plugin_data.database.register_events(&insert).await {
-                return Err(format!("Unable to insert into Database: {}", e));
+
+            if let Err(e) = self
+                .plugin_data
+                .database
+                .get_events::<DatabaseCommit>()
+                .delete_many(
+                    Database::combine_documents(
+                        kind_filter("commit"),
+                        doc! {
+                            "id": {
+                                "$in": rejected
+                            }
+                        },
+                    ),
+                    None,
+                )
+                .await
+            {
+                return Err(format!("Unable to remove filtered commits: {}", e));
             }
         }
 
@@ -311,16 +2047,637 @@ impl Plugin {
 struct Commit {
     id: String,
     message: String,
-    author: String,
-    time: DateTime<Utc>,
+    author_name: String,
+    author_email: String,
+    author_time: DateTime<Utc>,
+    author_offset_minutes: i32,
+    committer_name: String,
+    committer_email: String,
+    committer_time: DateTime<Utc>,
+    committer_offset_minutes: i32,
     repository_name: String,
+    refs: Vec<String>,
+    diff: Option<DiffSummary>,
+}
+
+impl Commit {
+    /// Same as [`DatabaseCommit::has_participant`], for commits that were just read.
+    fn has_participant(&self, authors: &AuthorFilter) -> bool {
+        authors.matches(&self.author_name, &self.author_email)
+            || authors.matches(&self.committer_name, &self.committer_email)
+            || parse_message(&self.message)
+                .co_authors()
+                .any(|(name, email)| authors.matches(name, email))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct DatabaseCommit {
     message: String,
-    author: String,
+    /// `Name <email>` string of commits stored before identities were split up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(default)]
+    author_name: String,
+    #[serde(default)]
+    author_email: String,
+    #[serde(default)]
+    author_time: Option<DateTime<Utc>>,
+    /// Timezone of the author in minutes east of UTC.
+    #[serde(default)]
+    author_offset_minutes: Option<i32>,
+    #[serde(default)]
+    committer_name: String,
+    #[serde(default)]
+    committer_email: String,
+    #[serde(default)]
+    committer_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    committer_offset_minutes: Option<i32>,
     repository_name: String,
+    /// The tracked references that contain the commit. Grows as branches are
+    /// merged or forked, references that lose the commit are not removed.
+    #[serde(default)]
+    refs: Vec<String>,
+    #[serde(default)]
+    diff: Option<DiffSummary>,
+    /// Set once the commit is no longer reachable from any tracked reference.
+    #[serde(default)]
+    rewritten: bool,
+    /// Event id of the commit that replaced this one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    replaced_by: Option<String>,
+    #[serde(flatten)]
+    parsed: ParsedMessage,
+    #[serde(default)]
+    references: Vec<IssueReference>,
+}
+
+/// The payload of the events handed to the client component.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum CompressedGitEvent {
+    Commit(Box<DatabaseCommit>),
+    Session(WorkSession),
+    Group(CommitGroup),
+    Tag(DatabaseTag),
+    Reflog(DatabaseReflogEntry),
+}
+
+/// Size of a commit compared to its first parent.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct DiffSummary {
+    files_changed: usize,
+    /// Not counted when the diff exceeds the configured file limit.
+    insertions: Option<usize>,
+    deletions: Option<usize>,
+    paths: Vec<String>,
+}
+
+impl DatabaseCommit {
+    /// Fills the author fields of commits stored in the legacy format.
+    fn upgrade_legacy_identity(&mut self) {
+        if let Some(author) = self.author.take() {
+            if self.author_name.is_empty() && self.author_email.is_empty() {
+                let (name, email) = split_signature(&author);
+                self.author_name = name.to_string();
+                self.author_email = email.to_string();
+            }
+        }
+    }
+
+    /// Parses the message of commits stored before messages were parsed.
+    fn upgrade_legacy_message(&mut self) {
+        if self.parsed.subject.is_empty() {
+            self.parsed = parse_message(&self.message);
+        }
+    }
+
+    /// Whether the author, the committer or one of the co-authors matches.
+    fn has_participant(&self, authors: &AuthorFilter) -> bool {
+        authors.matches(&self.author_name, &self.author_email)
+            || authors.matches(&self.committer_name, &self.committer_email)
+            || self
+                .parsed
+                .co_authors()
+                .any(|(name, email)| authors.matches(name, email))
+    }
+}
+
+fn get_diff_summary(
+    repo: &Repository,
+    commit: &git2::Commit,
+    limits: &DiffStatsConfig,
+) -> Result<DiffSummary, git2::Error> {
+    let tree = commit.tree()?;
+    let parent_tree = match commit.parent(0) {
+        Ok(v) => Some(v.tree()?),
+        Err(_) => None,
+    };
+
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+    let files_changed = diff.deltas().len();
+    let paths = diff
+        .deltas()
+        .filter_map(|v| v.new_file().path().or(v.old_file().path()))
+        .take(limits.max_paths)
+        .map(|v| v.to_string_lossy().to_string())
+        .collect();
+
+    let (insertions, deletions) = match files_changed > limits.max_files {
+        true => (None, None),
+        false => {
+            let stats = diff.stats()?;
+            (Some(stats.insertions()), Some(stats.deletions()))
+        }
+    };
+
+    Ok(DiffSummary {
+        files_changed,
+        insertions,
+        deletions,
+        paths,
+    })
+}
+
+fn commit_timing(
+    author_time: DateTime<Utc>,
+    committer_time: DateTime<Utc>,
+    source: TimestampSource,
+) -> Timing {
+    match source {
+        TimestampSource::Author => Timing::Instant(author_time),
+        TimestampSource::Committer => Timing::Instant(committer_time),
+        TimestampSource::Both if author_time == committer_time => Timing::Instant(committer_time),
+        TimestampSource::Both => Timing::Range(types::timing::TimeRange {
+            start: author_time.min(committer_time),
+            end: author_time.max(committer_time),
+        }),
+    }
+}
+
+/// The moment an event ended, which is the commit time for single commits.
+fn event_end(timing: &Timing) -> DateTime<Utc> {
+    match timing {
+        Timing::Instant(v) => *v,
+        Timing::Range(v) => v.end,
+    }
+}
+
+/// The time as it was on the clock of whoever made the commit. Git stores the
+/// offset in minutes east of UTC, broken offsets are read as UTC.
+fn git_time_to_local(time: git2::Time) -> DateTime<FixedOffset> {
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)
+        .unwrap_or(FixedOffset::east_opt(0).unwrap());
+    offset.timestamp_millis_opt(time.seconds() * 1000).unwrap()
+}
+
+fn git_time_to_utc(time: git2::Time) -> DateTime<Utc> {
+    DateTime::<Utc>::from(git_time_to_local(time))
+}
+
+/// Explicitly configured paths are compared against discovered ones after
+/// resolving symlinks and relative components, where possible.
+fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+/// Mirror clones copy every upstream ref verbatim, so their `refs/remotes`
+/// are stale copies of the upstream's own remote-tracking branches.
+fn is_mirror(repo: &Repository) -> Result<bool, git2::Error> {
+    let config = repo.config()?;
+    Ok(repo.remotes()?.iter().flatten().any(|remote| {
+        config
+            .get_bool(&format!("remote.{}.mirror", remote))
+            .unwrap_or(false)
+    }))
+}
+
+/// The name of a reference as it is shown, e.g. `main`, `origin/main` or `v1.0`.
+fn short_ref_name(name: &str) -> &str {
+    ["refs/heads/", "refs/remotes/", "refs/tags/", "refs/"]
+        .iter()
+        .find_map(|v| name.strip_prefix(v))
+        .unwrap_or(name)
+}
+
+/// Commits that `tip` reaches but `previous` did not, newest first and down to `since`.
+fn newly_contained(
+    repo: &Repository,
+    tip: git2::Oid,
+    previous: Option<git2::Oid>,
+    since: Option<DateTime<Utc>>,
+) -> Result<Vec<git2::Oid>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.push(tip)?;
+    walk.set_sorting(git2::Sort::TIME)?;
+    if let Some(previous) = previous {
+        walk.hide(previous)?;
+    }
+
+    let mut commits = Vec::new();
+    for step in walk {
+        let oid = step?;
+        if let Some(since) = since {
+            if git_time_to_utc(repo.find_commit(oid)?.time()) < since {
+                break;
+            }
+        }
+        commits.push(oid);
+    }
+    Ok(commits)
+}
+
+/// Resolves the selected references to the commits they point at, keyed by
+/// their full name, or `HEAD` if it is detached. References that do not point
+/// at a commit are skipped.
+fn get_ref_tips(
+    repo: &Repository,
+    selection: &RefSelection,
+) -> Result<BTreeMap<String, git2::Oid>, git2::Error> {
+    let mut tips = BTreeMap::new();
+
+    let mut selection = selection.clone();
+    if repo.is_bare() {
+        selection.remote_branches = !is_mirror(repo)?;
+    }
+
+    if selection.head {
+        match repo.head() {
+            Ok(head) => {
+                if let Ok(commit) = head.peel_to_commit() {
+                    let name = match repo.head_detached()? {
+                        true => "HEAD".to_string(),
+                        false => head.name().unwrap_or("HEAD").to_string(),
+                    };
+                    tips.insert(name, commit.id());
+                }
+            }
+            // An unborn branch has no commits yet, the other refs can still be walked.
+            Err(e) if matches!(e.code(), git2::ErrorCode::UnbornBranch | git2::ErrorCode::NotFound) => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    for glob in selection.globs() {
+        for reference in repo.references_glob(glob)? {
+            let reference = reference?;
+            if reference.kind() == Some(git2::ReferenceType::Symbolic) {
+                continue;
+            }
+            let Ok(commit) = reference.peel_to_commit() else {
+                continue;
+            };
+            let Some(name) = reference.name() else {
+                continue;
+            };
+            tips.insert(name.to_string(), commit.id());
+        }
+    }
+
+    Ok(tips)
+}
+
+/// Everything this plugin stores, told apart by `event.kind`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum DatabaseGitEvent {
+    Commit(Box<DatabaseCommit>),
+    Tag(DatabaseTag),
+    Reflog(DatabaseReflogEntry),
+    State(StoredState),
+}
+
+type GitEvent = Event<DatabaseGitEvent>;
+
+/// The references a commit event was found through, other events have none.
+fn event_refs(event: &GitEvent) -> &[String] {
+    match &event.event {
+        DatabaseGitEvent::Commit(v) => &v.refs,
+        _ => &[],
+    }
+}
+
+/// The [`ScanState`], kept as the only event of kind `state`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct StoredState {
+    /// Serialized as JSON, repository paths are no valid field names in every database version.
+    json: String,
+}
+
+const STATE_ID: &str = "timeline_plugin_git:state";
+
+/// Events whose ids are looked up with one query.
+const UPSERT_CHUNK_SIZE: usize = 500;
+
+/// Error code of a write rejected by a unique index.
+const DUPLICATE_KEY: i32 = 11000;
+
+async fn load_state(database: &Database) -> Result<Option<ScanState>, String> {
+    let stored = match database
+        .get_events::<DatabaseGitEvent>()
+        .find_one(
+            Database::combine_documents(
+                kind_filter("state"),
+                doc! {
+                    "id": STATE_ID
+                },
+            ),
+            None,
+        )
+        .await
+    {
+        Ok(v) => v,
+        Err(e) => return Err(format!("Error loading scan state from database: {}", e)),
+    };
+
+    match stored.map(|v| v.event) {
+        Some(DatabaseGitEvent::State(v)) => match serde_json::from_str(&v.json) {
+            Ok(v) => Ok(Some(v)),
+            Err(e) => Err(format!("Unable to parse scan state: {}", e)),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Matches the stored events of one kind of this plugin.
+fn kind_filter(kind: &str) -> Document {
+    Database::combine_documents(
+        Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_git),
+        doc! {
+            "event.kind": kind
+        },
+    )
+}
+
+/// The configured name of an explicit repository entry, or else its directory name.
+fn repository_name(path: &Path, repository: &RepositoryConfig) -> Result<String, String> {
+    match &repository.name {
+        Some(v) => Ok(v.clone()),
+        None => match path.file_name() {
+            Some(v) => Ok(strip_git_suffix(&v.to_string_lossy()).to_string()),
+            None => Err(format!(
+                "Unable to identify repository name. There is no last path component. \nPath: {}",
+                path.display()
+            )),
+        },
+    }
+}
+
+/// Commits are identified by repository and object id.
+fn commit_event_id(repository_name: &str, oid: git2::Oid) -> String {
+    format!("{}:{}", repository_name, oid)
+}
+
+/// Inverse of [`commit_event_id`].
+fn commit_oid(repository_name: &str, id: &str) -> Option<git2::Oid> {
+    id.strip_prefix(repository_name)?
+        .strip_prefix(':')
+        .and_then(|v| git2::Oid::from_str(v).ok())
+}
+
+/// Moves commits stored under their bare object id to [`commit_event_id`], drops
+/// duplicates left behind by earlier scans and makes event ids of this plugin unique.
+/// Bare repositories were stored as `project.git` before the suffix was dropped
+/// from derived names. Their events are renamed, both the `repository_name` and
+/// the prefix of the id, so they are not stored a second time under the new name.
+/// Events that were stored under both names already keep the new one.
+async fn strip_stored_git_suffixes(database: &Database, keep: &[&str]) -> Result<(), String> {
+    let collection = database
+        .get_events::<DatabaseGitEvent>()
+        .clone_with_type::<Document>();
+
+    let names = match collection
+        .distinct(
+            "event.repository_name",
+            Database::combine_documents(
+                Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_git),
+                doc! {
+                    "event.repository_name": {
+                        "$regex": "\\.git$"
+                    }
+                },
+            ),
+            None,
+        )
+        .await
+    {
+        Ok(v) => v,
+        Err(e) => return Err(format!("Unable to look up stored repository names: {}", e)),
+    };
+
+    for old_name in names.iter().filter_map(|v| v.as_str()) {
+        let new_name = strip_git_suffix(old_name);
+        if new_name == old_name || keep.contains(&old_name) {
+            continue;
+        }
+        let (old_prefix, new_prefix) = (format!("{}:", old_name), format!("{}:", new_name));
+        let repository_filter = Database::combine_documents(
+            Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_git),
+            doc! {
+                "event.repository_name": old_name
+            },
+        );
+
+        let old_ids: Vec<String> = match collection
+            .find(
+                repository_filter.clone(),
+                FindOptions::builder().projection(doc! { "id": 1 }).build(),
+            )
+            .await
+        {
+            Ok(v) => match v.try_collect::<Vec<Document>>().await {
+                Ok(v) => v
+                    .iter()
+                    .filter_map(|v| v.get_str("id").ok())
+                    .filter(|v| v.starts_with(&old_prefix))
+                    .map(String::from)
+                    .collect(),
+                Err(e) => return Err(format!("Unable to collect stored events: {}", e)),
+            },
+            Err(e) => return Err(format!("Unable to load stored events: {}", e)),
+        };
+
+        // Renaming an event whose new id is taken would break the unique index.
+        for chunk in old_ids.chunks(UPSERT_CHUNK_SIZE) {
+            let new_ids: Vec<String> = chunk
+                .iter()
+                .map(|v| v.replacen(&old_prefix, &new_prefix, 1))
+                .collect();
+            let taken: Vec<String> = match collection
+                .distinct(
+                    "id",
+                    Database::combine_documents(
+                        Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_git),
+                        doc! {
+                            "id": {
+                                "$in": new_ids
+                            }
+                        },
+                    ),
+                    None,
+                )
+                .await
+            {
+                Ok(v) => v
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .map(|v| v.replacen(&new_prefix, &old_prefix, 1))
+                    .collect(),
+                Err(e) => return Err(format!("Unable to look up renamed events: {}", e)),
+            };
+            if taken.is_empty() {
+                continue;
+            }
+
+            if let Err(e) = collection
+                .delete_many(
+                    Database::combine_documents(
+                        repository_filter.clone(),
+                        doc! {
+                            "id": {
+                                "$in": taken
+                            }
+                        },
+                    ),
+                    None,
+                )
+                .await
+            {
+                return Err(format!("Unable to remove events stored under both names: {}", e));
+            }
+        }
+
+        if let Err(e) = collection
+            .update_many(
+                repository_filter,
+                vec![doc! {
+                    "$set": {
+                        "event.repository_name": new_name,
+                        "id": {
+                            "$replaceOne": {
+                                "input": "$id",
+                                "find": &old_prefix,
+                                "replacement": &new_prefix
+                            }
+                        }
+                    }
+                }],
+                None,
+            )
+            .await
+        {
+            return Err(format!("Unable to rename stored events of {}: {}", old_name, e));
+        }
+    }
+
+    Ok(())
 }
 
-type CommitEvent = Event<DatabaseCommit>;
+async fn ensure_commit_identity(database: &Database) -> Result<(), String> {
+    let collection = database.get_events::<DatabaseGitEvent>();
+
+    if let Err(e) = collection
+        .update_many(
+            Database::combine_documents(
+                kind_filter("commit"),
+                doc! {
+                    "id": {
+                        "$regex": "^[0-9a-f]{40}([0-9a-f]{24})?$"
+                    }
+                },
+            ),
+            vec![doc! {
+                "$set": {
+                    "id": {
+                        "$concat": ["$event.repository_name", ":", "$id"]
+                    }
+                }
+            }],
+            None,
+        )
+        .await
+    {
+        return Err(format!("Unable to rename stored commits: {}", e));
+    }
+
+    let duplicates: Vec<Document> = match collection
+        .aggregate(
+            vec![
+                doc! {
+                    "$match": Database::generate_find_plugin_filter(AvailablePlugins::timeline_plugin_git)
+                },
+                doc! {
+                    "$group": {
+                        "_id": "$id",
+                        "documents": {
+                            "$push": "$_id"
+                        },
+                        "count": {
+                            "$sum": 1
+                        }
+                    }
+                },
+                doc! {
+                    "$match": {
+                        "count": {
+                            "$gt": 1
+                        }
+                    }
+                },
+            ],
+            None,
+        )
+        .await
+    {
+        Ok(v) => match v.try_collect().await {
+            Ok(v) => v,
+            Err(e) => return Err(format!("Unable to collect duplicated events: {}", e)),
+        },
+        Err(e) => return Err(format!("Unable to look for duplicated events: {}", e)),
+    };
+
+    for duplicate in duplicates {
+        let Ok(documents) = duplicate.get_array("documents") else {
+            continue;
+        };
+        if let Err(e) = collection
+            .delete_many(
+                doc! {
+                    "_id": {
+                        "$in": documents[1..].to_vec()
+                    }
+                },
+                None,
+            )
+            .await
+        {
+            return Err(format!("Unable to remove duplicated events: {}", e));
+        }
+    }
+
+    if let Err(e) = collection
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! {
+                    "id": 1
+                })
+                .options(
+                    IndexOptions::builder()
+                        .name("timeline_plugin_git_id".to_string())
+                        .unique(true)
+                        .partial_filter_expression(Database::generate_find_plugin_filter(
+                            AvailablePlugins::timeline_plugin_git,
+                        ))
+                        .build(),
+                )
+                .build(),
+            None,
+        )
+        .await
+    {
+        return Err(format!("Unable to create unique index on event ids: {}", e));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use {
+    crate::authors::split_signature,
+    encoding_rs::{Encoding, UTF_8, WINDOWS_1252},
+    regex::Regex,
+    serde::{Deserialize, Serialize},
+    std::sync::OnceLock,
+};
+
+/// Decodes a commit message according to the commit's `encoding` header.
+///
+/// Messages without a header are expected to be UTF-8. If they are not, they
+/// are read as Windows-1252, which covers the latin-1 messages of old imports.
+/// A warning is returned whenever the message could not be decoded cleanly.
+pub fn decode_message(bytes: &[u8], encoding: Option<&str>) -> (String, Option<String>) {
+    let (encoding, mut warning) = match encoding {
+        Some(label) => match Encoding::for_label(label.trim().as_bytes()) {
+            Some(v) => (v, None),
+            None => (
+                UTF_8,
+                Some(format!("Unknown message encoding {}, reading it as UTF-8", label)),
+            ),
+        },
+        None => match std::str::from_utf8(bytes) {
+            Ok(v) => return (v.to_string(), None),
+            Err(_) => (
+                WINDOWS_1252,
+                Some("Message is not valid UTF-8, reading it as Windows-1252".to_string()),
+            ),
+        },
+    };
+
+    let (message, _, had_errors) = encoding.decode(bytes);
+    if had_errors && warning.is_none() {
+        warning = Some(format!(
+            "Message is not valid {}, invalid characters were replaced",
+            encoding.name()
+        ));
+    }
+
+    (message.to_string(), warning)
+}
+
+/// A commit message split into its parts.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ParsedMessage {
+    /// The first line.
+    #[serde(default)]
+    pub subject: String,
+    /// Everything between the subject and the trailers.
+    #[serde(default)]
+    pub body: Option<String>,
+    /// `Key: value` lines of the last paragraph, such as `Co-authored-by` or `Fixes`.
+    #[serde(default)]
+    pub trailers: Vec<Trailer>,
+    #[serde(default)]
+    pub conventional: Option<ConventionalCommit>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Trailer {
+    pub key: String,
+    pub value: String,
+}
+
+/// The header of a [Conventional Commits](https://www.conventionalcommits.org)
+/// message, e.g. `feat(api)!: drop the v1 endpoints`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConventionalCommit {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub scope: Option<String>,
+    /// Marked with `!` or a `BREAKING CHANGE` trailer.
+    pub breaking: bool,
+    pub description: String,
+}
+
+impl ParsedMessage {
+    /// Name and email of every `Co-authored-by` trailer.
+    pub fn co_authors(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.trailers
+            .iter()
+            .filter(|v| v.key.eq_ignore_ascii_case("co-authored-by"))
+            .map(|v| split_signature(&v.value))
+    }
+}
+
+pub fn parse_message(message: &str) -> ParsedMessage {
+    let message = message.trim();
+    let (subject, rest) = match message.split_once('\n') {
+        Some((subject, rest)) => (subject.trim(), rest.trim()),
+        None => (message, ""),
+    };
+
+    // Trailers make up the last paragraph, which has to be separate from the subject.
+    let mut paragraphs: Vec<&str> = rest.split("\n\n").map(|v| v.trim()).collect();
+    let trailers = match paragraphs.last().and_then(|v| parse_trailers(v)) {
+        Some(v) => {
+            paragraphs.pop();
+            v
+        }
+        None => Vec::new(),
+    };
+    let body = paragraphs.join("\n\n").trim().to_string();
+
+    let conventional = CONVENTIONAL_HEADER
+        .get_or_init(|| {
+            Regex::new(r"^(?<type>[A-Za-z]+)(?:\((?<scope>[^()]*)\))?(?<breaking>!)?: (?<description>.+)$")
+                .unwrap()
+        })
+        .captures(subject)
+        .map(|v| ConventionalCommit {
+            kind: v["type"].to_lowercase(),
+            scope: v.name("scope").map(|v| v.as_str().to_string()),
+            breaking: v.name("breaking").is_some()
+                || trailers
+                    .iter()
+                    .any(|v| v.key == "BREAKING CHANGE" || v.key == "BREAKING-CHANGE"),
+            description: v["description"].to_string(),
+        });
+
+    ParsedMessage {
+        subject: subject.to_string(),
+        body: match body.is_empty() {
+            true => None,
+            false => Some(body),
+        },
+        trailers,
+        conventional,
+    }
+}
+
+static CONVENTIONAL_HEADER: OnceLock<Regex> = OnceLock::new();
+
+/// Reads a paragraph of `Key: value` lines. Indented lines continue the previous
+/// value. Returns `None` if any other line is part of the paragraph.
+fn parse_trailers(paragraph: &str) -> Option<Vec<Trailer>> {
+    let mut trailers: Vec<Trailer> = Vec::new();
+    for line in paragraph.lines() {
+        if line.starts_with([' ', '\t']) {
+            trailers.last_mut()?.value.push_str(&format!(" {}", line.trim()));
+            continue;
+        }
+
+        let (key, value) = line.split_once(": ")?;
+        let is_key = key == "BREAKING CHANGE"
+            || (!key.is_empty() && key.chars().all(|v| v.is_ascii_alphanumeric() || v == '-'));
+        if !is_key {
+            return None;
+        }
+        trailers.push(Trailer {
+            key: key.to_string(),
+            value: value.trim().to_string(),
+        });
+    }
+
+    match trailers.is_empty() {
+        true => None,
+        false => Some(trailers),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_utf8_without_warning() {
+        assert_eq!(decode_message("café".as_bytes(), None), ("café".to_string(), None));
+    }
+
+    #[test]
+    fn falls_back_to_windows_1252() {
+        let (message, warning) = decode_message(b"caf\xe9", None);
+        assert_eq!(message, "café");
+        assert!(warning.is_some());
+    }
+
+    #[test]
+    fn follows_the_encoding_header() {
+        assert_eq!(decode_message(b"caf\xe9", Some("ISO-8859-1")), ("café".to_string(), None));
+        assert_eq!(
+            decode_message(b"\x83e\x83X\x83g", Some("Shift_JIS")),
+            ("テスト".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn warns_about_unknown_and_invalid_encodings() {
+        let (message, warning) = decode_message(b"plain", Some("no-such-encoding"));
+        assert_eq!(message, "plain");
+        assert!(warning.is_some());
+
+        let (message, warning) = decode_message(b"caf\xe9", Some("UTF-8"));
+        assert_eq!(message, "caf\u{fffd}");
+        assert!(warning.is_some());
+    }
+
+    #[test]
+    fn splits_subject_body_and_trailers() {
+        let parsed = parse_message(
+            "Add login\n\nFirst paragraph.\n\nSecond paragraph.\n\nCo-authored-by: Jane <jane@example.com>\nFixes: #12\n  and #13\n",
+        );
+        assert_eq!(parsed.subject, "Add login");
+        assert_eq!(parsed.body.as_deref(), Some("First paragraph.\n\nSecond paragraph."));
+        assert_eq!(parsed.trailers.len(), 2);
+        assert_eq!(parsed.trailers[1].key, "Fixes");
+        assert_eq!(parsed.trailers[1].value, "#12 and #13");
+        assert_eq!(parsed.co_authors().collect::<Vec<_>>(), vec![("Jane", "jane@example.com")]);
+        assert!(parsed.conventional.is_none());
+    }
+
+    #[test]
+    fn keeps_paragraphs_with_prose_in_the_body() {
+        let parsed = parse_message("Update docs\n\nNote: the last paragraph\nis prose, not trailers.");
+        assert!(parsed.trailers.is_empty());
+        assert_eq!(
+            parsed.body.as_deref(),
+            Some("Note: the last paragraph\nis prose, not trailers.")
+        );
+    }
+
+    #[test]
+    fn parses_conventional_headers() {
+        let parsed = parse_message("Feat(api): add the v2 endpoints");
+        let conventional = parsed.conventional.unwrap();
+        assert_eq!(conventional.kind, "feat");
+        assert_eq!(conventional.scope.as_deref(), Some("api"));
+        assert!(!conventional.breaking);
+        assert_eq!(conventional.description, "add the v2 endpoints");
+
+        let conventional = parse_message("fix!: drop v1").conventional.unwrap();
+        assert!(conventional.breaking);
+        assert!(conventional.scope.is_none());
+
+        assert!(parse_message("Merge branch 'main'").conventional.is_none());
+    }
+
+    #[test]
+    fn breaking_change_trailer_marks_breaking() {
+        let parsed = parse_message("refactor: rename settings\n\nBREAKING CHANGE: `foo` is now `bar`");
+        assert_eq!(parsed.trailers[0].key, "BREAKING CHANGE");
+        assert!(parsed.conventional.unwrap().breaking);
+
+        let parsed = parse_message("refactor: rename settings\n\nBREAKING-CHANGE: `foo` is now `bar`");
+        assert!(parsed.conventional.unwrap().breaking);
+    }
+}
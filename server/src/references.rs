@@ -0,0 +1,168 @@
+use {
+    regex::Regex,
+    serde::{Deserialize, Serialize},
+};
+
+/// Recognises issue or pull request references such as `#123` or `JIRA-456` in
+/// commit messages.
+#[derive(Deserialize, Clone)]
+pub struct ReferencePattern {
+    /// Stored with every reference, so the `url` can be changed later on.
+    pub name: String,
+    /// A regular expression. Its `id` group, or else its first group, is the id
+    /// of the reference. Without groups the whole match is used.
+    pub pattern: String,
+    /// Link template, `{id}` and `{repository}` are replaced.
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+/// A reference found in a commit message.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IssueReference {
+    /// Name of the pattern that found it.
+    pub pattern: String,
+    /// The matched text, e.g. `#123`.
+    pub text: String,
+    pub id: String,
+    /// Only filled when events are loaded, from the current configuration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Default)]
+pub struct ReferenceExtractor {
+    patterns: Vec<(ReferencePattern, Regex)>,
+}
+
+impl ReferenceExtractor {
+    pub fn new(patterns: &[ReferencePattern]) -> Result<Self, String> {
+        let patterns = patterns
+            .iter()
+            .map(|v| {
+                Regex::new(&v.pattern)
+                    .map(|regex| (v.clone(), regex))
+                    .map_err(|e| format!("{}: {}", v.name, e))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(ReferenceExtractor { patterns })
+    }
+
+    /// Every distinct reference in `message`, in the order of the patterns.
+    pub fn extract(&self, message: &str) -> Vec<IssueReference> {
+        let mut references: Vec<IssueReference> = Vec::new();
+        for (pattern, regex) in &self.patterns {
+            for captures in regex.captures_iter(message) {
+                let text = &captures[0];
+                let id = captures
+                    .name("id")
+                    .or_else(|| captures.get(1))
+                    .map_or(text, |v| v.as_str());
+
+                if references
+                    .iter()
+                    .any(|v| v.pattern == pattern.name && v.id == id)
+                {
+                    continue;
+                }
+                references.push(IssueReference {
+                    pattern: pattern.name.clone(),
+                    text: text.to_string(),
+                    id: id.to_string(),
+                    url: None,
+                });
+            }
+        }
+        references
+    }
+
+    /// Builds the links of stored references from the configured templates.
+    pub fn link(&self, references: &mut [IssueReference], repository_name: &str) {
+        for reference in references {
+            reference.url = self
+                .patterns
+                .iter()
+                .find(|(v, _)| v.name == reference.pattern)
+                .and_then(|(v, _)| v.url.as_ref())
+                .map(|v| {
+                    v.replace("{id}", &reference.id)
+                        .replace("{repository}", repository_name)
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(name: &str, pattern: &str, url: Option<&str>) -> ReferencePattern {
+        ReferencePattern {
+            name: name.to_string(),
+            pattern: pattern.to_string(),
+            url: url.map(String::from),
+        }
+    }
+
+    fn ids(references: &[IssueReference]) -> Vec<(&str, &str)> {
+        references
+            .iter()
+            .map(|v| (v.text.as_str(), v.id.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn captures_the_id_group_first() {
+        let extractor =
+            ReferenceExtractor::new(&[pattern("jira", r"(?<project>[A-Z]+)-(?<id>\d+)", None)]).unwrap();
+        assert_eq!(ids(&extractor.extract("Fix ABC-12 and XY-3")), vec![("ABC-12", "12"), ("XY-3", "3")]);
+    }
+
+    #[test]
+    fn falls_back_to_the_first_group_and_the_whole_match() {
+        let extractor = ReferenceExtractor::new(&[
+            pattern("github", r"#(\d+)", None),
+            pattern("cve", r"CVE-\d{4}-\d+", None),
+        ])
+        .unwrap();
+        assert_eq!(
+            ids(&extractor.extract("Closes #7, see CVE-2024-1234")),
+            vec![("#7", "7"), ("CVE-2024-1234", "CVE-2024-1234")]
+        );
+    }
+
+    #[test]
+    fn drops_duplicates_of_the_same_pattern() {
+        let extractor = ReferenceExtractor::new(&[
+            pattern("github", r"#(\d+)", None),
+            pattern("pr", r"(?:#|PR )(\d+)", None),
+        ])
+        .unwrap();
+        let references = extractor.extract("#7 again #7, PR 7");
+        assert_eq!(
+            references
+                .iter()
+                .map(|v| (v.pattern.as_str(), v.id.as_str()))
+                .collect::<Vec<_>>(),
+            vec![("github", "7"), ("pr", "7")]
+        );
+    }
+
+    #[test]
+    fn links_from_the_current_templates() {
+        let extractor = ReferenceExtractor::new(&[
+            pattern("github", r"#(\d+)", Some("https://example.com/{repository}/issues/{id}")),
+            pattern("plain", r"T(\d+)", None),
+        ])
+        .unwrap();
+        let mut references = extractor.extract("#7 and T9");
+        extractor.link(&mut references, "app");
+        assert_eq!(references[0].url.as_deref(), Some("https://example.com/app/issues/7"));
+        assert_eq!(references[1].url, None);
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        assert!(ReferenceExtractor::new(&[pattern("broken", "(", None)]).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use {
+    crate::git_time_to_utc,
+    git2::Repository,
+    serde::{Deserialize, Serialize},
+    server_api::external::types::external::chrono::{DateTime, Utc},
+    std::collections::HashMap,
+};
+
+/// A local action on a branch or HEAD, taken from the reflog.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabaseReflogEntry {
+    pub repository_name: String,
+    /// `HEAD` or the short name of a local branch.
+    pub reference: String,
+    pub action: ReflogAction,
+    pub old: String,
+    pub new: String,
+    pub message: String,
+    /// Position in the reflog counted from its oldest entry. Positions shift once
+    /// old entries expire, so they are not part of the event id.
+    pub index: usize,
+    #[serde(default)]
+    pub committer_name: String,
+    #[serde(default)]
+    pub committer_email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReflogAction {
+    Checkout,
+    Commit,
+    Rebase,
+    Merge,
+    Pull,
+    Reset,
+    FastForward,
+    Created,
+    Deleted,
+    Other,
+}
+
+impl ReflogAction {
+    /// Classifies the messages git writes into the reflog, e.g. `checkout: moving
+    /// from main to feature` or `pull: Fast-forward`.
+    pub fn from_message(message: &str) -> Self {
+        let fast_forward = message.contains("Fast-forward");
+        match message.split([':', ' ']).next().unwrap_or_default() {
+            "checkout" => ReflogAction::Checkout,
+            "commit" => ReflogAction::Commit,
+            "rebase" => ReflogAction::Rebase,
+            "merge" | "pull" if fast_forward => ReflogAction::FastForward,
+            "merge" => ReflogAction::Merge,
+            "pull" => ReflogAction::Pull,
+            "reset" => ReflogAction::Reset,
+            "branch" if message.starts_with("branch: Created") => ReflogAction::Created,
+            _ => ReflogAction::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReflogEntry {
+    pub id: String,
+    pub time: DateTime<Utc>,
+    pub entry: DatabaseReflogEntry,
+}
+
+/// Reads the reflogs of HEAD and every local branch. Entries older than the
+/// timestamp recorded in `since` for their reference were read before and are skipped.
+pub fn read_reflogs(
+    repo: &Repository,
+    repo_name: &str,
+    since: &HashMap<String, i64>,
+) -> Result<Vec<ReflogEntry>, git2::Error> {
+    let mut references = vec![("HEAD".to_string(), "HEAD".to_string())];
+    for reference in repo.references_glob("refs/heads/*")? {
+        let reference = reference?;
+        if let (Some(name), Some(shorthand)) = (reference.name(), reference.shorthand()) {
+            references.push((name.to_string(), shorthand.to_string()));
+        }
+    }
+
+    let mut entries = Vec::new();
+    for (name, shorthand) in references {
+        let reflog = repo.reflog(&name)?;
+        let since = since.get(&shorthand).copied().unwrap_or(i64::MIN);
+
+        // The reflog is ordered from the newest entry to the oldest one.
+        for (position, entry) in reflog.iter().enumerate() {
+            let committer = entry.committer();
+            if committer.when().seconds() < since {
+                break;
+            }
+
+            let message = entry
+                .message_bytes()
+                .map(|v| String::from_utf8_lossy(v).to_string())
+                .unwrap_or_default();
+
+            entries.push(ReflogEntry {
+                id: format!(
+                    "{}:{}:{}:{}..{}",
+                    repo_name,
+                    shorthand,
+                    committer.when().seconds(),
+                    entry.id_old(),
+                    entry.id_new()
+                ),
+                time: git_time_to_utc(committer.when()),
+                entry: DatabaseReflogEntry {
+                    repository_name: repo_name.to_string(),
+                    reference: shorthand.clone(),
+                    action: ReflogAction::from_message(&message),
+                    old: entry.id_old().to_string(),
+                    new: entry.id_new().to_string(),
+                    message,
+                    index: reflog.len() - 1 - position,
+                    committer_name: String::from_utf8_lossy(committer.name_bytes()).to_string(),
+                    committer_email: String::from_utf8_lossy(committer.email_bytes())
+                        .to_string(),
+                },
+            });
+        }
+    }
+
+    Ok(entries)
+}
+
+/// The tips of all local branches, used to notice deleted branches whose
+/// reflog is gone together with them.
+pub fn local_branch_tips(repo: &Repository) -> Result<HashMap<String, String>, git2::Error> {
+    let mut tips = HashMap::new();
+    for reference in repo.references_glob("refs/heads/*")? {
+        let reference = reference?;
+        if let (Some(shorthand), Some(target)) = (reference.shorthand(), reference.target()) {
+            tips.insert(shorthand.to_string(), target.to_string());
+        }
+    }
+    Ok(tips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify(message: &str) -> ReflogAction {
+        ReflogAction::from_message(message)
+    }
+
+    #[test]
+    fn classifies_by_the_command() {
+        assert_eq!(classify("checkout: moving from main to feature"), ReflogAction::Checkout);
+        assert_eq!(classify("commit: Add parser"), ReflogAction::Commit);
+        assert_eq!(classify("commit (amend): Add parser"), ReflogAction::Commit);
+        assert_eq!(classify("commit (initial): Initial commit"), ReflogAction::Commit);
+        assert_eq!(classify("rebase (finish): returning to refs/heads/feature"), ReflogAction::Rebase);
+        assert_eq!(classify("rebase -i (start): checkout main"), ReflogAction::Rebase);
+        assert_eq!(classify("reset: moving to HEAD~1"), ReflogAction::Reset);
+    }
+
+    #[test]
+    fn classifies_fast_forwards_before_merges_and_pulls() {
+        assert_eq!(classify("merge feature: Fast-forward"), ReflogAction::FastForward);
+        assert_eq!(classify("pull: Fast-forward"), ReflogAction::FastForward);
+        assert_eq!(
+            classify("merge feature: Merge made by the 'ort' strategy."),
+            ReflogAction::Merge
+        );
+        assert_eq!(
+            classify("pull: Merge made by the 'ort' strategy."),
+            ReflogAction::Pull
+        );
+        assert_eq!(
+            classify("pull --rebase (finish): returning to refs/heads/main"),
+            ReflogAction::Pull
+        );
+    }
+
+    #[test]
+    fn only_counts_created_branches() {
+        assert_eq!(classify("branch: Created from HEAD"), ReflogAction::Created);
+        assert_eq!(classify("branch: Created from main"), ReflogAction::Created);
+        assert_eq!(
+            classify("branch: renamed refs/heads/old to refs/heads/new"),
+            ReflogAction::Other
+        );
+    }
+
+    #[test]
+    fn falls_back_to_other() {
+        assert_eq!(classify("clone: from https://example.com/app.git"), ReflogAction::Other);
+        assert_eq!(classify("cherry-pick: Add parser"), ReflogAction::Other);
+        assert_eq!(classify("Fast-forward"), ReflogAction::Other);
+        assert_eq!(classify(""), ReflogAction::Other);
+    }
+}
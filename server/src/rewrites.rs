@@ -0,0 +1,41 @@
+use {
+    git2::{Oid, Repository},
+    serde::Deserialize,
+    std::collections::HashSet,
+};
+
+/// What happens to stored commits that are no longer reachable from any tracked
+/// reference, e.g. after an amend, a rebase or a force-push.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum RewritePolicy {
+    /// Leave them as they are.
+    #[default]
+    Keep,
+    Delete,
+    /// Keep them, flagged as rewritten.
+    Mark,
+    /// Flag them and point them at the commit that replaced them, matched by patch-id.
+    Link,
+}
+
+/// Every commit reachable from one of `tips`.
+pub fn reachable_commits(repo: &Repository, tips: &[Oid]) -> Result<HashSet<Oid>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    for tip in tips {
+        walk.push(*tip)?;
+    }
+    walk.collect()
+}
+
+/// The patch-id of a commit's change against its first parent. A rebased or
+/// amended commit keeps it as long as its change stays the same.
+pub fn patch_id(repo: &Repository, oid: Oid) -> Result<Oid, git2::Error> {
+    let commit = repo.find_commit(oid)?;
+    let parent_tree = match commit.parent(0) {
+        Ok(v) => Some(v.tree()?),
+        Err(_) => None,
+    };
+    let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+    diff.patchid(None)
+}
@@ -0,0 +1,155 @@
+use {
+    serde::Deserialize,
+    server_api::external::types::external::chrono::{DateTime, Duration, Utc},
+};
+
+/// How often repositories are scanned.
+#[derive(Deserialize, Clone)]
+pub struct PollingConfig {
+    #[serde(default = "default_interval_minutes")]
+    pub interval_minutes: i64,
+    /// Scans recently active repositories more often than dormant ones and backs
+    /// off from repositories that keep failing.
+    #[serde(default)]
+    pub adaptive: bool,
+    /// Adaptive mode only, the interval for repositories with commits from the last hours.
+    #[serde(default = "default_min_interval_minutes")]
+    pub min_interval_minutes: i64,
+    /// Adaptive mode only, the interval for dormant and failing repositories.
+    #[serde(default = "default_max_interval_minutes")]
+    pub max_interval_minutes: i64,
+}
+
+impl Default for PollingConfig {
+    fn default() -> Self {
+        PollingConfig {
+            interval_minutes: default_interval_minutes(),
+            adaptive: false,
+            min_interval_minutes: default_min_interval_minutes(),
+            max_interval_minutes: default_max_interval_minutes(),
+        }
+    }
+}
+
+fn default_interval_minutes() -> i64 {
+    15
+}
+
+fn default_min_interval_minutes() -> i64 {
+    2
+}
+
+fn default_max_interval_minutes() -> i64 {
+    24 * 60
+}
+
+impl PollingConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_minutes < 1 || self.min_interval_minutes < 1 {
+            return Err("Polling intervals have to be at least one minute".to_string());
+        }
+        if self.min_interval_minutes > self.max_interval_minutes {
+            return Err(format!(
+                "min_interval_minutes ({}) is larger than max_interval_minutes ({})",
+                self.min_interval_minutes, self.max_interval_minutes
+            ));
+        }
+        Ok(())
+    }
+
+    /// How often the plugin checks whether repositories are due for a scan.
+    pub fn tick(&self) -> Duration {
+        match self.adaptive {
+            true => Duration::try_minutes(self.min_interval_minutes).unwrap(),
+            false => Duration::try_minutes(self.interval_minutes).unwrap(),
+        }
+    }
+
+    /// The time until the next scan of a repository whose newest commit is from
+    /// `last_activity` and whose last `failures` scans failed in a row.
+    ///
+    /// A repository is polled once per 24 minutes of inactivity, so one that was
+    /// untouched for a day is scanned hourly. Every failure doubles the interval.
+    pub fn next_interval(&self, last_activity: Option<DateTime<Utc>>, failures: u32) -> Duration {
+        let min = Duration::try_minutes(self.min_interval_minutes).unwrap();
+        let max = Duration::try_minutes(self.max_interval_minutes).unwrap();
+
+        let interval = match last_activity {
+            Some(v) => (Utc::now() - v) / 24,
+            None => Duration::try_minutes(self.interval_minutes).unwrap(),
+        }
+        .clamp(min, max);
+
+        interval
+            .checked_mul(2_i32.pow(failures.min(16)))
+            .unwrap_or(max)
+            .min(max)
+    }
+}
+
+/// When a repository is scanned next, only used in adaptive mode.
+#[derive(Default)]
+pub struct RepositorySchedule {
+    pub next_scan: DateTime<Utc>,
+    pub last_activity: Option<DateTime<Utc>>,
+    pub failures: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adaptive() -> PollingConfig {
+        PollingConfig {
+            interval_minutes: 15,
+            adaptive: true,
+            min_interval_minutes: 2,
+            max_interval_minutes: 24 * 60,
+        }
+    }
+
+    fn minutes(v: i64) -> Duration {
+        Duration::try_minutes(v).unwrap()
+    }
+
+    #[test]
+    fn scales_with_inactivity() {
+        let config = adaptive();
+        let interval = config.next_interval(Some(Utc::now() - Duration::try_days(1).unwrap()), 0);
+        assert!((interval - minutes(60)).abs() < minutes(1));
+    }
+
+    #[test]
+    fn clamps_to_bounds() {
+        let config = adaptive();
+        assert_eq!(config.next_interval(Some(Utc::now()), 0), minutes(2));
+        assert_eq!(
+            config.next_interval(Some(Utc::now() - Duration::try_days(365).unwrap()), 0),
+            minutes(24 * 60)
+        );
+    }
+
+    #[test]
+    fn unknown_activity_uses_the_fixed_interval() {
+        assert_eq!(adaptive().next_interval(None, 0), minutes(15));
+    }
+
+    #[test]
+    fn failures_back_off_up_to_the_maximum() {
+        let config = adaptive();
+        assert_eq!(config.next_interval(None, 1), minutes(30));
+        assert_eq!(config.next_interval(None, 3), minutes(120));
+        assert_eq!(config.next_interval(None, 10), minutes(24 * 60));
+        assert_eq!(config.next_interval(None, u32::MAX), minutes(24 * 60));
+    }
+
+    #[test]
+    fn rejects_invalid_intervals() {
+        assert!(adaptive().validate().is_ok());
+        assert!(PollingConfig { interval_minutes: 0, ..adaptive() }.validate().is_err());
+        assert!(PollingConfig { min_interval_minutes: 0, ..adaptive() }.validate().is_err());
+        assert!(PollingConfig { min_interval_minutes: 60, max_interval_minutes: 30, ..adaptive() }
+            .validate()
+            .is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use {
+    crate::{event_end, CompressedGitEvent, DatabaseCommit},
+    serde::{Deserialize, Serialize},
+    server_api::{
+        db::Event,
+        external::types::{
+            api::CompressedEvent,
+            external::{
+                chrono::{DateTime, Duration, Utc},
+                serde_json,
+            },
+            timing::{TimeRange, Timing},
+        },
+    },
+    std::collections::HashMap,
+};
+
+/// Merges consecutive commits into work sessions instead of showing them one by one.
+#[derive(Deserialize, Clone)]
+pub struct SessionConfig {
+    /// Commits further apart than this start a new session.
+    #[serde(default = "default_max_gap_minutes")]
+    pub max_gap_minutes: i64,
+    /// Work that happened before the first commit of a session, which is
+    /// added in front of it.
+    #[serde(default = "default_lead_in_minutes")]
+    pub lead_in_minutes: i64,
+}
+
+fn default_max_gap_minutes() -> i64 {
+    120
+}
+
+fn default_lead_in_minutes() -> i64 {
+    30
+}
+
+impl SessionConfig {
+    pub fn max_gap(&self) -> Duration {
+        Duration::try_minutes(self.max_gap_minutes).unwrap_or_default()
+    }
+
+    pub fn lead_in(&self) -> Duration {
+        Duration::try_minutes(self.lead_in_minutes).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WorkSession {
+    pub repository_name: String,
+    pub author_name: String,
+    pub author_email: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub commits: Vec<DatabaseCommit>,
+}
+
+type TimedCommit = (DateTime<Utc>, DatabaseCommit);
+
+/// Groups commits by repository and author and splits every group wherever two
+/// commits are further apart than the configured gap. Only sessions overlapping
+/// `range` are returned, the commits should be loaded back to the first gap in
+/// front of `range` and up to the first gap after it so sessions crossing its
+/// borders are complete.
+pub fn build_sessions(
+    commits: Vec<Event<DatabaseCommit>>,
+    config: &SessionConfig,
+    range: &TimeRange,
+) -> Vec<CompressedEvent> {
+    let mut by_author: HashMap<(String, String), Vec<TimedCommit>> = HashMap::new();
+    for commit in commits {
+        let key = (
+            commit.event.repository_name.clone(),
+            match commit.event.author_email.is_empty() {
+                true => commit.event.author_name.clone(),
+                false => commit.event.author_email.to_lowercase(),
+            },
+        );
+        by_author
+            .entry(key)
+            .or_default()
+            .push((event_end(&commit.timing), commit.event));
+    }
+
+    let mut sessions = Vec::new();
+    for (_, mut commits) in by_author {
+        commits.sort_by_key(|(time, _)| *time);
+
+        let mut current: Vec<TimedCommit> = Vec::new();
+        for commit in commits {
+            if let Some((last, _)) = current.last() {
+                if commit.0 - *last > config.max_gap() {
+                    sessions.push(std::mem::take(&mut current));
+                }
+            }
+            current.push(commit);
+        }
+        if !current.is_empty() {
+            sessions.push(current);
+        }
+    }
+
+    sessions
+        .into_iter()
+        .filter_map(|commits| {
+            let start = commits.first()?.0 - config.lead_in();
+            let end = commits.last()?.0;
+            if end < range.start || start > range.end {
+                return None;
+            }
+
+            let first = &commits.first()?.1;
+            let session = WorkSession {
+                repository_name: first.repository_name.clone(),
+                author_name: first.author_name.clone(),
+                author_email: first.author_email.clone(),
+                start,
+                end,
+                commits: commits.into_iter().map(|(_, v)| v).collect(),
+            };
+
+            Some(CompressedEvent {
+                title: session.repository_name.clone(),
+                time: Timing::Range(TimeRange { start, end }),
+                data: serde_json::to_value(CompressedGitEvent::Session(session)).unwrap(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        server_api::external::types::{
+            available_plugins::AvailablePlugins,
+            external::{chrono::TimeZone, serde_json::json},
+        },
+    };
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 1, hour, minute, 0).unwrap()
+    }
+
+    fn commit(author: &str, time: DateTime<Utc>) -> Event<DatabaseCommit> {
+        Event {
+            timing: Timing::Instant(time),
+            id: format!("app:{}:{}", author, time.timestamp()),
+            plugin: AvailablePlugins::timeline_plugin_git,
+            event: serde_json::from_value(json!({
+                "message": "work",
+                "repository_name": "app",
+                "author_name": author,
+                "author_email": format!("{}@Example.com", author),
+            }))
+            .unwrap(),
+        }
+    }
+
+    fn config() -> SessionConfig {
+        SessionConfig {
+            max_gap_minutes: 120,
+            lead_in_minutes: 30,
+        }
+    }
+
+    fn whole_day() -> TimeRange {
+        TimeRange {
+            start: at(0, 0),
+            end: at(23, 59),
+        }
+    }
+
+    /// Start, end and number of commits of every session, in order.
+    fn summary(events: Vec<CompressedEvent>) -> Vec<(DateTime<Utc>, DateTime<Utc>, usize)> {
+        let mut sessions: Vec<_> = events
+            .into_iter()
+            .map(|v| {
+                let Timing::Range(range) = v.time else {
+                    panic!("sessions span a range");
+                };
+                (range.start, range.end, v.data["commits"].as_array().unwrap().len())
+            })
+            .collect();
+        sessions.sort();
+        sessions
+    }
+
+    #[test]
+    fn splits_at_gaps_longer_than_the_maximum() {
+        let commits = vec![
+            commit("jane", at(10, 0)),
+            commit("jane", at(12, 0)),
+            commit("jane", at(14, 1)),
+        ];
+        assert_eq!(
+            summary(build_sessions(commits, &config(), &whole_day())),
+            vec![(at(9, 30), at(12, 0), 2), (at(13, 31), at(14, 1), 1)]
+        );
+    }
+
+    #[test]
+    fn adds_the_lead_in_in_front_of_the_first_commit() {
+        let config = SessionConfig {
+            lead_in_minutes: 45,
+            ..config()
+        };
+        assert_eq!(
+            summary(build_sessions(vec![commit("jane", at(10, 0))], &config, &whole_day())),
+            vec![(at(9, 15), at(10, 0), 1)]
+        );
+    }
+
+    #[test]
+    fn keeps_authors_apart() {
+        let commits = vec![
+            commit("jane", at(10, 0)),
+            commit("john", at(10, 10)),
+            commit("jane", at(10, 20)),
+        ];
+        assert_eq!(
+            summary(build_sessions(commits, &config(), &whole_day())),
+            vec![(at(9, 30), at(10, 20), 2), (at(9, 40), at(10, 10), 1)]
+        );
+    }
+
+    #[test]
+    fn returns_sessions_overlapping_the_range() {
+        let range = TimeRange {
+            start: at(12, 0),
+            end: at(16, 0),
+        };
+        let commits = vec![
+            // Ends before the range.
+            commit("jane", at(8, 0)),
+            // Crosses its start.
+            commit("jane", at(11, 0)),
+            commit("jane", at(12, 30)),
+            // Only its lead-in reaches into the range.
+            commit("jane", at(16, 20)),
+            // Starts after the range.
+            commit("john", at(17, 0)),
+        ];
+        assert_eq!(
+            summary(build_sessions(commits, &config(), &range)),
+            vec![(at(10, 30), at(12, 30), 2), (at(15, 50), at(16, 20), 1)]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use {
+    crate::{authors::AuthorFilter, git_time_to_utc},
+    git2::{Mailmap, Oid, Repository, Signature, Time},
+    serde::{Deserialize, Serialize},
+    server_api::external::types::external::chrono::{DateTime, Utc},
+};
+
+/// A release or any other tag, stored next to the commits.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabaseTag {
+    pub tag_name: String,
+    pub repository_name: String,
+    /// The commit the tag points at.
+    pub target: String,
+    pub annotated: bool,
+    #[serde(default)]
+    pub tagger_name: Option<String>,
+    #[serde(default)]
+    pub tagger_email: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tag {
+    pub id: String,
+    /// Tag time of annotated tags, the target commit's time for lightweight ones.
+    pub time: DateTime<Utc>,
+    pub tag: DatabaseTag,
+}
+
+/// Reads every tag of a repository that points at a commit.
+pub fn read_tags(repo: &Repository, repo_name: &str) -> Result<Vec<Tag>, git2::Error> {
+    let mut tags = Vec::new();
+
+    for reference in repo.references_glob("refs/tags/*")? {
+        let reference = reference?;
+        let Some(tag_name) = reference.shorthand().map(|v| v.to_string()) else {
+            continue;
+        };
+        let Ok(commit) = reference.peel_to_commit() else {
+            continue;
+        };
+
+        let (time, tag) = match reference.peel_to_tag() {
+            Ok(annotated) => {
+                let tagger = annotated.tagger();
+                (
+                    git_time_to_utc(tagger.as_ref().map(|v| v.when()).unwrap_or(commit.time())),
+                    DatabaseTag {
+                        tag_name,
+                        repository_name: repo_name.to_string(),
+                        target: commit.id().to_string(),
+                        annotated: true,
+                        tagger_name: tagger
+                            .as_ref()
+                            .map(|v| String::from_utf8_lossy(v.name_bytes()).to_string()),
+                        tagger_email: tagger
+                            .as_ref()
+                            .map(|v| String::from_utf8_lossy(v.email_bytes()).to_string()),
+                        message: annotated
+                            .message_bytes()
+                            .map(|v| String::from_utf8_lossy(v).trim_end().to_string()),
+                    },
+                )
+            }
+            Err(_) => (
+                git_time_to_utc(commit.time()),
+                DatabaseTag {
+                    tag_name,
+                    repository_name: repo_name.to_string(),
+                    target: commit.id().to_string(),
+                    annotated: false,
+                    tagger_name: None,
+                    tagger_email: None,
+                    message: None,
+                },
+            ),
+        };
+
+        tags.push(Tag {
+            // A tag that is moved to another commit is a new release.
+            id: format!("{}:{}:{}", tag.repository_name, tag.tag_name, tag.target),
+            time,
+            tag,
+        });
+    }
+
+    Ok(tags)
+}
+
+/// Whether `authors` match the tagger of an annotated tag, or the author or
+/// committer of the commit a lightweight tag points at.
+pub fn is_tagged_by(
+    repo: &Repository,
+    mailmap: Option<&Mailmap>,
+    tag: &DatabaseTag,
+    authors: &AuthorFilter,
+) -> bool {
+    let resolve = |v: Signature<'_>| match mailmap {
+        Some(mailmap) => mailmap.resolve_signature(&v).unwrap_or_else(|_| v.to_owned()),
+        None => v.to_owned(),
+    };
+    let matches = |v: &Signature<'_>| {
+        authors.matches(
+            &String::from_utf8_lossy(v.name_bytes()),
+            &String::from_utf8_lossy(v.email_bytes()),
+        )
+    };
+
+    if tag.annotated {
+        let name = tag.tagger_name.as_deref().unwrap_or_default();
+        let email = tag.tagger_email.as_deref().unwrap_or_default();
+        return match Signature::new(name, email, &Time::new(0, 0)) {
+            Ok(v) => matches(&resolve(v)),
+            Err(_) => authors.matches(name, email),
+        };
+    }
+
+    let Ok(commit) = Oid::from_str(&tag.target).and_then(|v| repo.find_commit(v)) else {
+        return false;
+    };
+    matches(&resolve(commit.author())) || matches(&resolve(commit.committer()))
+}
@@ -0,0 +1,212 @@
+use {
+    git2::Repository,
+    notify::{RecommendedWatcher, RecursiveMode, Watcher},
+    serde::Deserialize,
+    std::{
+        collections::{HashMap, HashSet},
+        path::{Path, PathBuf},
+        sync::{
+            mpsc::{self, Receiver},
+            Mutex,
+        },
+        time::{Duration, Instant},
+    },
+};
+
+/// Scans a repository as soon as its refs change instead of waiting for the next poll.
+#[derive(Deserialize, Clone)]
+pub struct WatchConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Changes are only acted upon once a repository was quiet for this long,
+    /// so a rebase or pull triggers one scan instead of dozens.
+    #[serde(default = "default_debounce_seconds")]
+    pub debounce_seconds: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        WatchConfig {
+            enabled: false,
+            debounce_seconds: default_debounce_seconds(),
+        }
+    }
+}
+
+fn default_debounce_seconds() -> u64 {
+    2
+}
+
+impl WatchConfig {
+    pub fn debounce(&self) -> Duration {
+        Duration::from_secs(self.debounce_seconds)
+    }
+}
+
+/// Watches `HEAD`, `packed-refs` and `refs` of every tracked repository and
+/// remembers which repositories changed when.
+pub struct RepositoryWatcher {
+    watcher: Mutex<RecommendedWatcher>,
+    /// Changed paths, sent by the watcher's own thread.
+    events: Mutex<Receiver<(PathBuf, Instant)>>,
+    /// Watched directory -> repositories it belongs to. Linked worktrees share
+    /// the `refs` of their main repository.
+    watched: Mutex<HashMap<PathBuf, Vec<PathBuf>>>,
+    /// Repository -> time of its latest change.
+    changes: Mutex<HashMap<PathBuf, Instant>>,
+}
+
+impl RepositoryWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+
+        // The handler only passes paths on. Taking any lock here could deadlock
+        // with `watch`, which waits for this thread while (un)registering paths.
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+
+            let now = Instant::now();
+            for path in event.paths.into_iter().filter(|v| is_ref_change(v)) {
+                let _ = sender.send((path, now));
+            }
+        })?;
+
+        Ok(RepositoryWatcher {
+            watcher: Mutex::new(watcher),
+            events: Mutex::new(events),
+            watched: Mutex::default(),
+            changes: Mutex::default(),
+        })
+    }
+
+    /// Starts watching new repositories and stops watching the ones that are gone.
+    /// Opens every new repository, so it should run on a blocking thread.
+    pub fn watch(&self, repositories: &[PathBuf]) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        let (gone, new, mut known) = {
+            let mut watched = self.watched.lock().unwrap();
+            let mut gone = Vec::new();
+            watched.retain(|dir, owners| {
+                owners.retain(|v| repositories.contains(v));
+                if owners.is_empty() {
+                    gone.push(dir.clone());
+                }
+                !owners.is_empty()
+            });
+            let new: Vec<PathBuf> = repositories
+                .iter()
+                .filter(|repository| !watched.values().any(|v| v.contains(repository)))
+                .cloned()
+                .collect();
+            let known: HashSet<PathBuf> = watched.keys().cloned().collect();
+            (gone, new, known)
+        };
+
+        let mut dirs = Vec::new();
+        for repository in new {
+            let git_dir = match Repository::open(&repository) {
+                Ok(v) => v.path().to_owned(),
+                Err(e) => {
+                    errors.push(format!(
+                        "Unable to watch repository: \nPath: {} \nError: {}",
+                        repository.display(),
+                        e
+                    ));
+                    continue;
+                }
+            };
+
+            // Linked worktrees keep their branches in the main repository.
+            let common_dir = match std::fs::read_to_string(git_dir.join("commondir")) {
+                Ok(v) => git_dir.join(v.trim()),
+                Err(_) => git_dir.clone(),
+            };
+
+            dirs.push((git_dir.clone(), RecursiveMode::NonRecursive, repository.clone()));
+            if common_dir != git_dir {
+                dirs.push((common_dir.clone(), RecursiveMode::NonRecursive, repository.clone()));
+            }
+            dirs.push((common_dir.join("refs"), RecursiveMode::Recursive, repository));
+        }
+
+        let mut added = Vec::new();
+        {
+            let mut watcher = self.watcher.lock().unwrap();
+            for dir in gone {
+                let _ = watcher.unwatch(&dir);
+            }
+            for (dir, mode, repository) in dirs {
+                // Directories shared with another repository are watched already.
+                if known.contains(&dir) {
+                    added.push((dir, repository));
+                    continue;
+                }
+                match watcher.watch(&dir, mode) {
+                    Ok(_) => {
+                        known.insert(dir.clone());
+                        added.push((dir, repository));
+                    }
+                    Err(e) => errors.push(format!(
+                        "Unable to watch repository: \nPath: {} \nError: {}",
+                        dir.display(),
+                        e
+                    )),
+                }
+            }
+        }
+        let mut watched = self.watched.lock().unwrap();
+        for (dir, repository) in added {
+            watched.entry(dir).or_default().push(repository);
+        }
+
+        errors
+    }
+
+    /// Returns the repositories whose last change is older than `debounce`.
+    pub fn take_settled(&self, debounce: Duration) -> Vec<PathBuf> {
+        let mut changes = self.changes.lock().unwrap();
+        {
+            let watched = self.watched.lock().unwrap();
+            for (path, time) in self.events.lock().unwrap().try_iter() {
+                // Submodules live inside the `.git/modules` of their parent, so
+                // the most specific watched directory is the right repository.
+                // Shared directories of worktrees belong to all of them.
+                if let Some((_, owners)) = watched
+                    .iter()
+                    .filter(|(dir, _)| path.starts_with(dir))
+                    .max_by_key(|(dir, _)| dir.components().count())
+                {
+                    for repository in owners {
+                        changes.insert(repository.clone(), time);
+                    }
+                }
+            }
+        }
+
+        let settled: Vec<PathBuf> = changes
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= debounce)
+            .map(|(repository, _)| repository.clone())
+            .collect();
+        for repository in &settled {
+            changes.remove(repository);
+        }
+        settled
+    }
+}
+
+/// Git rewrites refs through lock files, which are renamed over the ref once
+/// they are complete. Only the final names are of interest.
+fn is_ref_change(path: &Path) -> bool {
+    if path.extension().is_some_and(|v| v == "lock") {
+        return false;
+    }
+
+    path.components().any(|v| v.as_os_str() == "refs")
+        || path
+            .file_name()
+            .is_some_and(|v| v == "HEAD" || v == "packed-refs")
+}